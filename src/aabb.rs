@@ -9,7 +9,12 @@ pub struct Aabb {
 }
 
 impl Aabb {
-  pub fn hit(&self, r: &Ray, mut tmin: f32, mut tmax: f32) -> bool {
+  pub fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> bool {
+    self.hit_range(r, tmin, tmax).is_some()
+  }
+
+  // the part of [tmin, tmax] where the ray is inside the box
+  pub fn hit_range(&self, r: &Ray, mut tmin: f32, mut tmax: f32) -> Option<(f32, f32)> {
     for a in 0..3 {
       let min_t = (self.min[a] - r.origin[a]) / r.direction[a];
       let max_t = (self.max[a] - r.origin[a]) / r.direction[a];
@@ -20,11 +25,11 @@ impl Aabb {
       tmax = ffmin(t1, tmax);
 
       if tmax <= tmin {
-        return false;
+        return None;
       }
     }
 
-    true
+    Some((tmin, tmax))
   }
 
   pub fn surrounding_box(&self, aabb: &Aabb) -> Aabb {
@@ -0,0 +1,151 @@
+// Materials wrapping another one to give it a shading normal different from
+// the geometric normal of the surface, for details too small to be modeled:
+// scratches, grain, bricks. The silhouettes stay smooth.
+//
+// A shading normal can make the surface look like it faces away from the
+// viewer, or send light through the surface where the geometry reflects it.
+// The geometric normal is used when the viewer is behind the shading one,
+// and directions on different sides of the two normals are dropped.
+
+use std::sync::Arc;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{HitRecord, tangent_towards, bitangent_towards};
+use material::{Material, BsdfSample};
+use texture::{Texture};
+
+// distance the height is sampled at around the hit, for its slope
+const BUMP_DELTA: f32 = 0.001;
+
+// the hit with `normal` as its shading normal
+fn shaded<'a>(ray: &Ray, rec: &HitRecord<'a>, normal: Vec3) -> HitRecord<'a> {
+    let wo = -ray.direction;
+    let normal =
+        if normal.squared_length() == 0. || wo.dot(normal) * wo.dot(rec.normal) <= 0. { rec.normal }
+        else { normal.unit_vector() };
+    let tangent = tangent_towards(normal, rec.tangent);
+    HitRecord {
+        normal,
+        tangent,
+        bitangent: bitangent_towards(normal, tangent, rec.bitangent),
+        ..*rec
+    }
+}
+
+// true when `direction` is on the same side of both normals
+fn consistent(rec: &HitRecord, shaded: &HitRecord, direction: Vec3) -> bool {
+    direction.dot(rec.normal) * direction.dot(shaded.normal) > 0.
+}
+
+// the material seen through `shaded`, with the directions on the wrong side dropped
+fn sample(material: &dyn Material, ray: &Ray, rec: &HitRecord, shaded: &HitRecord) -> Option<BsdfSample> {
+    let sample = material.sample(ray, shaded)?;
+    if consistent(rec, shaded, sample.direction) { Some(sample) } else { None }
+}
+
+fn eval(material: &dyn Material, ray: &Ray, rec: &HitRecord, shaded: &HitRecord, direction: Vec3) -> Vec3 {
+    if consistent(rec, shaded, direction) { material.eval(ray, shaded, direction) } else { Vec3::zero() }
+}
+
+fn pdf(material: &dyn Material, ray: &Ray, rec: &HitRecord, shaded: &HitRecord, direction: Vec3) -> f32 {
+    if consistent(rec, shaded, direction) { material.pdf(ray, shaded, direction) } else { 0. }
+}
+
+// Bumps from the first channel of a height texture, displacing the surface
+// along its normal by `strength` times the height. The height is looked up
+// around the hit both in space and in texture coordinates, taking u and v to
+// change as fast as the distance along the tangent and the bitangent, so it
+// suits procedural textures like noise as well as images.
+pub struct BumpMap {
+    pub material: Arc<dyn Material>,
+    pub height: Box<dyn Texture>,
+    pub strength: f32,
+}
+
+impl BumpMap {
+    pub fn new(material: Arc<dyn Material>, height: Box<dyn Texture>, strength: f32) -> Self {
+        BumpMap {
+            material,
+            height,
+            strength,
+        }
+    }
+
+    fn shaded<'a>(&self, ray: &Ray, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let (tangent, bitangent) = (rec.tangent, rec.bitangent);
+        let height = |u: f32, v: f32, p: Vec3| self.height.value(u, v, p).x * self.strength;
+
+        // slopes of the height along the tangent and the bitangent
+        let h = height(rec.u, rec.v, rec.point);
+        let dh_dt = (height(rec.u + BUMP_DELTA, rec.v, rec.point + tangent * BUMP_DELTA) - h) / BUMP_DELTA;
+        let dh_db = (height(rec.u, rec.v + BUMP_DELTA, rec.point + bitangent * BUMP_DELTA) - h) / BUMP_DELTA;
+
+        shaded(ray, rec, rec.normal - tangent * dh_dt - bitangent * dh_db)
+    }
+}
+
+impl Material for BumpMap {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        sample(&*self.material, ray, rec, &self.shaded(ray, rec))
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        eval(&*self.material, ray, rec, &self.shaded(ray, rec), direction)
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        pdf(&*self.material, ray, rec, &self.shaded(ray, rec), direction)
+    }
+
+    fn is_specular(&self, rec: &HitRecord) -> bool {
+        self.material.is_specular(rec)
+    }
+
+    fn emitted(&self, ray: &Ray, rec: &HitRecord) -> Vec3 {
+        self.material.emitted(ray, rec)
+    }
+}
+
+// Normals stored in a texture, usually an image, in the tangent space of
+// the surface: red along the tangent, green along the bitangent (where v
+// increases) and blue along the normal, each mapped from [-1, 1] to [0, 1].
+pub struct NormalMap {
+    pub material: Arc<dyn Material>,
+    pub normals: Box<dyn Texture>,
+}
+
+impl NormalMap {
+    pub fn new(material: Arc<dyn Material>, normals: Box<dyn Texture>) -> Self {
+        NormalMap {
+            material,
+            normals,
+        }
+    }
+
+    fn shaded<'a>(&self, ray: &Ray, rec: &HitRecord<'a>) -> HitRecord<'a> {
+        let local = self.normals.value(rec.u, rec.v, rec.point) * 2. - Vec3::fromf(1.);
+        shaded(ray, rec, rec.tangent * local.x + rec.bitangent * local.y + rec.normal * local.z)
+    }
+}
+
+impl Material for NormalMap {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        sample(&*self.material, ray, rec, &self.shaded(ray, rec))
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        eval(&*self.material, ray, rec, &self.shaded(ray, rec), direction)
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        pdf(&*self.material, ray, rec, &self.shaded(ray, rec), direction)
+    }
+
+    fn is_specular(&self, rec: &HitRecord) -> bool {
+        self.material.is_specular(rec)
+    }
+
+    fn emitted(&self, ray: &Ray, rec: &HitRecord) -> Vec3 {
+        self.material.emitted(ray, rec)
+    }
+}
@@ -19,7 +19,7 @@ struct BvhNode<'a> {
   left: Option<NodeId>,
   right: Option<NodeId>,
   aabb: Option<Aabb>,
-  hitable: Option<&'a Box<dyn Hitable>>
+  hitable: Option<&'a dyn Hitable>
 }
 
 impl<'a> Hitable for BvhNode<'a> {
@@ -27,7 +27,7 @@ impl<'a> Hitable for BvhNode<'a> {
     self.aabb
   }
 
-  fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+  fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
     match self.hitable {
       Some(hitable) => hitable.hit(r, t_min, t_max),
       None => None
@@ -36,7 +36,7 @@ impl<'a> Hitable for BvhNode<'a> {
 }
 
 impl<'a> BvhTree<'a> {
-  pub fn new(list: &'a mut[Box<dyn Hitable>], time0: f32, time1: f32) -> BvhTree {
+  pub fn new(list: &'a mut[Box<dyn Hitable>], time0: f32, time1: f32) -> BvhTree<'a> {
     let mut tree = BvhTree {
       nodes: Vec::new(),
       root: NodeId { index: 0 }
@@ -49,19 +49,19 @@ impl<'a> BvhTree<'a> {
     let axis = rand::thread_rng().gen_range::<i32>(0, 3);
     
     match axis {
-      0 => list.sort_by(|a, b| box_x_compare(a, b)),
-      1 => list.sort_by(|a, b| box_y_compare(a, b)),
-      2 => list.sort_by(|a, b| box_z_compare(a, b)),
+      0 => list.sort_by(|a, b| box_x_compare(&**a, &**b)),
+      1 => list.sort_by(|a, b| box_y_compare(&**a, &**b)),
+      2 => list.sort_by(|a, b| box_z_compare(&**a, &**b)),
       _ => panic!("Random axis out of range"),
     };
 
     match list.len() {
       1 => {
-        self.new_leaf(&list[0], time0, time1)
+        self.new_leaf(&*list[0], time0, time1)
       },
       2 => {
-        let left = self.new_leaf(&list[0], time0, time1);
-        let right = self.new_leaf(&list[1], time0, time1);
+        let left = self.new_leaf(&*list[0], time0, time1);
+        let right = self.new_leaf(&*list[1], time0, time1);
         let box_left = self.nodes[left.index].bounding_box(time0, time1);
         let box_right = self.nodes[right.index].bounding_box(time0, time1);
 
@@ -94,7 +94,7 @@ impl<'a> BvhTree<'a> {
     }
   }
 
-  fn new_leaf (&mut self, hitable: &'a Box<dyn Hitable>, time0: f32, time1: f32) -> NodeId {
+  fn new_leaf (&mut self, hitable: &'a dyn Hitable, time0: f32, time1: f32) -> NodeId {
     let node = BvhNode {
       left: None,
       right: None,
@@ -110,15 +110,15 @@ impl<'a> BvhTree<'a> {
     NodeId { index }
   }
 
-  fn hit_node(&self, node_id: &NodeId, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+  fn hit_node(&self, node_id: &NodeId, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
     let node = &self.nodes[node_id.index];
     if let Some(aabb) = node.aabb {
       if aabb.hit(r, t_min, t_max) {
         match (&node.left, &node.right) {
           (Some(left), Some(right)) => {
             match (
-              self.hit_node(&left, r, t_min, t_max),
-              self.hit_node(&right, r, t_min, t_max)
+              self.hit_node(left, r, t_min, t_max),
+              self.hit_node(right, r, t_min, t_max)
             ) {
               (Some(left_rec), Some(right_rec)) => 
                 if left_rec.t < right_rec.t {
@@ -131,8 +131,8 @@ impl<'a> BvhTree<'a> {
               (None, None) => None,
             }
           },
-          (Some(left), None) => self.hit_node(&left, r, t_min, t_max),
-          (None, Some(right)) => self.hit_node(&right, r, t_min, t_max),
+          (Some(left), None) => self.hit_node(left, r, t_min, t_max),
+          (None, Some(right)) => self.hit_node(right, r, t_min, t_max),
           (None, None) => node.hit(r, t_min, t_max)
         }
       } else {
@@ -142,6 +142,31 @@ impl<'a> BvhTree<'a> {
       None
     }
   }
+
+  // product of the transmittances of all the leaves the ray goes through
+  fn transmittance_node(&self, node_id: &NodeId, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+    let node = &self.nodes[node_id.index];
+    match node.aabb {
+      Some(aabb) if aabb.hit(r, t_min, t_max) => {
+        match (&node.left, &node.right) {
+          (Some(left), Some(right)) => {
+            let left_transmittance = self.transmittance_node(left, r, t_min, t_max);
+            if left_transmittance == 0. {
+              return 0.;
+            }
+            left_transmittance * self.transmittance_node(right, r, t_min, t_max)
+          },
+          (Some(left), None) => self.transmittance_node(left, r, t_min, t_max),
+          (None, Some(right)) => self.transmittance_node(right, r, t_min, t_max),
+          (None, None) => match node.hitable {
+            Some(hitable) => hitable.transmittance(r, t_min, t_max),
+            None => 1.,
+          }
+        }
+      },
+      _ => 1.
+    }
+  }
 }
 
 impl<'a> Hitable for BvhTree<'a> {
@@ -149,12 +174,16 @@ impl<'a> Hitable for BvhTree<'a> {
     self.nodes[self.root.index].bounding_box(t0, t1)
   }
   
-  fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+  fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
     self.hit_node(&self.root, r, t_min, t_max)
   }
+
+  fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+    self.transmittance_node(&self.root, r, t_min, t_max)
+  }
 }
 
-fn box_x_compare(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
+fn box_x_compare(a: &dyn Hitable, b: &dyn Hitable) -> Ordering {
   match (a.bounding_box(0.0, 0.0), b.bounding_box(0.0, 0.0)) {
     (Some(aabb0), Some(aabb1)) => {
       if let Some(cmp) = aabb0.min.x.partial_cmp(&aabb1.min.x) {
@@ -169,7 +198,7 @@ fn box_x_compare(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
   }
 }
 
-fn box_y_compare(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
+fn box_y_compare(a: &dyn Hitable, b: &dyn Hitable) -> Ordering {
   match (a.bounding_box(0.0, 0.0), b.bounding_box(0.0, 0.0)) {
     (Some(aabb0), Some(aabb1)) => {
       if let Some(cmp) = aabb0.min.y.partial_cmp(&aabb1.min.y) {
@@ -184,7 +213,7 @@ fn box_y_compare(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
   }
 }
 
-fn box_z_compare(a: &Box<dyn Hitable>, b: &Box<dyn Hitable>) -> Ordering {
+fn box_z_compare(a: &dyn Hitable, b: &dyn Hitable) -> Ordering {
   match (a.bounding_box(0.0, 0.0), b.bounding_box(0.0, 0.0)) {
     (Some(aabb0), Some(aabb1)) => {
       if let Some(cmp) = aabb0.min.z.partial_cmp(&aabb1.min.z) {
@@ -3,16 +3,7 @@ use self::rand::Rng;
 use vec3::{Vec3};
 use ray::{Ray};
 use std::f32::consts::PI;
-
-fn random_point_in_unit_disk() -> Vec3 {
-    let mut rng = rand::thread_rng();
-    let mut p: Vec3;
-    loop {
-        p = 2. * Vec3::new(rng.gen(), rng.gen(), 0.) - Vec3::new(1., 1., 0.);
-        if p.dot(p) < 1.0 { break; }
-    }
-    p
-}
+use sampling;
 
 pub struct Camera {
     origin: Vec3,
@@ -34,8 +25,7 @@ impl Camera {
         vertical_fov: f32,
         ratio: f32,
         aperture: f32,
-        time0: f32,
-        time1: f32,
+        (time0, time1): (f32, f32),
     ) -> Self {
         let distance_to_focus = (look_at - look_from).length();
         let lens_radius = aperture / 2.;
@@ -69,7 +59,7 @@ impl Camera {
     }
 
     pub fn get_ray(&self, u: f32, v: f32) -> Ray {
-        let rand_origin = self.lens_radius * random_point_in_unit_disk();
+        let rand_origin = self.lens_radius * sampling::random_in_unit_disk();
         let offset = rand_origin.x * self.u + rand_origin.y * self.v;
 
         let ray_origin = self.origin + offset;
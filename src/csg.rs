@@ -0,0 +1,316 @@
+// Constructive solid geometry. `Hitable::hit` only gives the closest surface,
+// which isn't enough to combine volumes: we need to know every span of the
+// ray that lies inside each solid. `Solid` exposes those spans, and `Csg`
+// merges the spans of two solids to produce the spans of the result.
+
+use std::f32;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, Sphere, MovingSphere};
+use quadrics::{Cylinder, Cone, Torus};
+use cuboid::{Cuboid};
+
+// a part of the ray that is inside a solid
+#[derive(Clone, Copy)]
+pub struct Interval<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+// distance under which two crossings are the same one, like a ray grazing
+// a rim finding it twice
+const CROSSING_EPSILON: f32 = 1e-4;
+
+// The intervals of a closed surface with outward normals, found by walking
+// its crossings one after the other with `hit`. The normals tell the ray
+// entering from the ray leaving, rather than counting the crossings, which
+// a ray touching the surface would throw off.
+fn crossing_intervals<'a, H: Hitable + ?Sized>(surface: &'a H, r: &Ray) -> Vec<Interval<'a>> {
+    let mut crossings: Vec<HitRecord> = vec![];
+    let mut t_min = f32::MIN;
+    while let Some(rec) = surface.hit(r, t_min, f32::MAX) {
+        t_min = rec.t;
+        if crossings.last().is_none_or(|last| rec.t - last.t > CROSSING_EPSILON) {
+            crossings.push(rec);
+        }
+    }
+
+    let tangent_cosine = CROSSING_EPSILON * r.direction.length();
+    let mut intervals = vec![];
+    let mut enter = None;
+    for rec in crossings {
+        let cosine = r.direction.dot(rec.normal);
+        match enter {
+            None if cosine < -tangent_cosine => enter = Some(rec),
+            Some(enter_rec) if cosine > tangent_cosine => {
+                intervals.push(Interval { enter: enter_rec, exit: rec });
+                enter = None;
+            },
+            // touching the surface, or crossing it the same way twice
+            _ => {},
+        }
+    }
+    intervals
+}
+
+pub trait Solid: Hitable {
+    // every interval where the ray's line is inside the solid,
+    // sorted along the ray.
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        crossing_intervals(self, r)
+    }
+}
+
+impl Solid for Sphere {}
+impl Solid for MovingSphere {}
+impl Solid for Cuboid {}
+impl Solid for Torus {}
+
+// Cylinders and cones are only closed, and thus solids, when they are
+// capped. Open ones have no inside, so they take no part in the operations.
+impl Solid for Cylinder {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        if self.capped { crossing_intervals(self, r) } else { vec![] }
+    }
+}
+
+impl Solid for Cone {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        if self.capped { crossing_intervals(self, r) } else { vec![] }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn is_inside(&self, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => inside_left || inside_right,
+            CsgOperation::Intersection => inside_left && inside_right,
+            CsgOperation::Difference => inside_left && !inside_right,
+        }
+    }
+}
+
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Box<dyn Solid>,
+    pub right: Box<dyn Solid>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Csg::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Csg::new(CsgOperation::Intersection, left, right)
+    }
+
+    // the left solid with the right one carved out of it
+    pub fn difference(left: Box<dyn Solid>, right: Box<dyn Solid>) -> Self {
+        Csg::new(CsgOperation::Difference, left, right)
+    }
+}
+
+// a surface crossing of one of the two operands
+struct Event<'a> {
+    rec: HitRecord<'a>,
+    is_left: bool,
+    is_enter: bool,
+}
+
+impl Solid for Csg {
+    fn intervals(&self, r: &Ray) -> Vec<Interval<'_>> {
+        let mut events: Vec<Event> = vec![];
+        for (is_left, solid) in [(true, &self.left), (false, &self.right)].iter() {
+            for interval in solid.intervals(r) {
+                events.push(Event { rec: interval.enter, is_left: *is_left, is_enter: true });
+                events.push(Event { rec: interval.exit, is_left: *is_left, is_enter: false });
+            }
+        }
+        events.sort_by(|a, b| a.rec.t.total_cmp(&b.rec.t));
+
+        let mut inside_left = false;
+        let mut inside_right = false;
+        let mut enter: Option<HitRecord> = None;
+        let mut intervals = vec![];
+
+        for event in events {
+            let was_inside = self.operation.is_inside(inside_left, inside_right);
+            if event.is_left {
+                inside_left = event.is_enter;
+            } else {
+                inside_right = event.is_enter;
+            }
+            let is_inside = self.operation.is_inside(inside_left, inside_right);
+
+            let mut rec = event.rec;
+            // the surface of the carved out solid faces the other way
+            if self.operation == CsgOperation::Difference && !event.is_left {
+                rec.normal = -rec.normal;
+            }
+
+            match (was_inside, is_inside, enter) {
+                (false, true, _) => enter = Some(rec),
+                (true, false, Some(enter_rec)) => {
+                    if rec.t > enter_rec.t {
+                        intervals.push(Interval { enter: enter_rec, exit: rec });
+                    }
+                    enter = None;
+                },
+                _ => {},
+            }
+        }
+
+        intervals
+    }
+}
+
+impl Hitable for Csg {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.intervals(r)
+            .into_iter()
+            .flat_map(|interval| vec![interval.enter, interval.exit])
+            .find(|rec| rec.t > t_min && rec.t < t_max)
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        let left = self.left.bounding_box(t0, t1);
+        let right = self.right.bounding_box(t0, t1);
+        match (self.operation, left, right) {
+            (CsgOperation::Union, Some(left), Some(right)) =>
+                Some(left.surrounding_box(&right)),
+            (CsgOperation::Intersection, Some(left), Some(right)) => {
+                let min = Vec3::new(
+                    left.min.x.max(right.min.x),
+                    left.min.y.max(right.min.y),
+                    left.min.z.max(right.min.z),
+                );
+                let max = Vec3::new(
+                    left.max.x.min(right.max.x),
+                    left.max.y.min(right.max.y),
+                    left.max.z.min(right.max.z),
+                );
+                // the operands don't overlap, leaving nothing
+                if min.x > max.x || min.y > max.y || min.z > max.z {
+                    None
+                } else {
+                    Some(Aabb { min, max })
+                }
+            },
+            (CsgOperation::Difference, left, _) => left,
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use material::{Lambertian};
+    use texture::{ConstantTexture};
+
+    fn cube(x: f32, y: f32) -> Box<dyn Solid> {
+        let material = Arc::new(Lambertian::new(Box::new(ConstantTexture::fromf(0.5))));
+        Box::new(Cuboid::new(Vec3::new(x - 0.5, y - 0.5, -0.5), Vec3::new(x + 0.5, y + 0.5, 0.5), material))
+    }
+
+    fn sphere(x: f32) -> Box<dyn Solid> {
+        let material = Arc::new(Lambertian::new(Box::new(ConstantTexture::fromf(0.5))));
+        Box::new(Sphere::new(Vec3::new(x, 0., 0.), 1., material))
+    }
+
+    fn ray(y: f32) -> Ray {
+        Ray { origin: Vec3::new(-10., y, 0.), direction: Vec3::new(1., 0., 0.), time: 0. }
+    }
+
+    // the x coordinates of the spans of `solid` along the line parallel to
+    // x through (0, y, 0)
+    fn spans(solid: &dyn Solid, y: f32) -> Vec<(f32, f32)> {
+        solid.intervals(&ray(y)).iter().map(|interval| (interval.enter.t - 10., interval.exit.t - 10.)).collect()
+    }
+
+    fn assert_spans(spans: Vec<(f32, f32)>, expected: &[(f32, f32)]) {
+        assert_eq!(spans.len(), expected.len(), "got {:?} instead of {:?}", spans, expected);
+        for (span, expected_span) in spans.iter().zip(expected) {
+            assert!(
+                (span.0 - expected_span.0).abs() < 1e-3 && (span.1 - expected_span.1).abs() < 1e-3,
+                "got {:?} instead of {:?}", spans, expected,
+            );
+        }
+    }
+
+    #[test]
+    fn union() {
+        assert_spans(spans(&Csg::union(sphere(-0.5), sphere(0.5)), 0.), &[(-1.5, 1.5)]);
+        assert_spans(spans(&Csg::union(sphere(-2.), sphere(2.)), 0.), &[(-3., -1.), (1., 3.)]);
+        assert_spans(spans(&Csg::union(sphere(0.), cube(0., 0.)), 0.), &[(-1., 1.)]);
+    }
+
+    #[test]
+    fn intersection() {
+        assert_spans(spans(&Csg::intersection(sphere(-0.5), sphere(0.5)), 0.), &[(-0.5, 0.5)]);
+        assert_spans(spans(&Csg::intersection(sphere(-2.), sphere(2.)), 0.), &[]);
+        assert_spans(spans(&Csg::intersection(sphere(0.), cube(1., 0.)), 0.), &[(0.5, 1.)]);
+    }
+
+    #[test]
+    fn difference() {
+        assert_spans(spans(&Csg::difference(sphere(0.), cube(0., 0.)), 0.), &[(-1., -0.5), (0.5, 1.)]);
+        assert_spans(spans(&Csg::difference(cube(0., 0.), sphere(1.)), 0.), &[(-0.5, 0.)]);
+        assert_spans(spans(&Csg::difference(cube(0., 0.), sphere(0.)), 0.), &[]);
+
+        // the carved out surfaces face out of the result
+        let solid = Csg::difference(sphere(0.), cube(0., 0.));
+        let intervals = solid.intervals(&ray(0.));
+        assert!(intervals[0].exit.normal.x > 0.99);
+        assert!(intervals[1].enter.normal.x < -0.99);
+    }
+
+    #[test]
+    fn touching_a_solid_leaves_the_ray_outside() {
+        // along the top of the sphere, touching it at a single point
+        assert_spans(spans(&*sphere(0.), 1.), &[]);
+        assert_spans(spans(&Csg::union(sphere(0.), cube(3., 1.)), 1.), &[(2.5, 3.5)]);
+        assert_spans(spans(&Csg::difference(cube(3., 1.), sphere(0.)), 1.), &[(2.5, 3.5)]);
+        assert_spans(spans(&Csg::intersection(sphere(0.), cube(0., 1.)), 1.), &[]);
+        assert_eq!(Csg::union(sphere(0.), cube(3., 1.)).hit(&ray(1.), 0., f32::MAX).map(|rec| rec.t), Some(12.5));
+    }
+
+    #[test]
+    fn rims_are_crossed_once() {
+        // rays entering a cylinder through the rim of its top, where the
+        // side and the cap are both hit, and leaving through the bottom
+        let material = Arc::new(Lambertian::new(Box::new(ConstantTexture::fromf(0.5))));
+        let cylinder = Cylinder::new(Vec3::zero(), 1., 1., material);
+        for i in 0..100 {
+            let angle = i as f32 * 0.01;
+            let direction = Vec3::new(-angle.cos(), -1., -angle.sin());
+            let r = Ray { origin: -direction * 2., direction, time: 0. };
+            let intervals: Vec<(f32, f32)> = cylinder.intervals(&r).iter().map(|interval| (interval.enter.t, interval.exit.t)).collect();
+            assert_spans(intervals, &[(1., 2.)]);
+        }
+    }
+
+    #[test]
+    fn disjoint_intersections_have_no_box() {
+        assert!(Csg::intersection(sphere(-2.), sphere(2.)).bounding_box(0., 1.).is_none());
+        assert!(Csg::intersection(sphere(-0.5), sphere(0.5)).bounding_box(0., 1.).is_some());
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::Arc;
+use std::f32;
+use std::mem;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, bitangent_towards};
+use material::{Material};
+
+// an axis aligned box
+#[derive(Clone)]
+pub struct Cuboid {
+    pub min: Vec3,
+    pub max: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cuboid {
+    pub fn new(min: Vec3, max: Vec3, material: Arc<dyn Material>) -> Self {
+        Cuboid {
+            min,
+            max,
+            material,
+        }
+    }
+}
+
+fn axis_vector(axis: usize, value: f32) -> Vec3 {
+    match axis {
+        0 => Vec3::new(value, 0., 0.),
+        1 => Vec3::new(0., value, 0.),
+        _ => Vec3::new(0., 0., value),
+    }
+}
+
+impl Hitable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        // slabs method, keeping track of the axis of the entry and exit faces
+        let mut t_enter = f32::MIN;
+        let mut t_exit = f32::MAX;
+        let mut enter_axis = 0;
+        let mut exit_axis = 0;
+
+        for a in 0..3 {
+            let inverse = 1. / ray.direction[a];
+            let mut t0 = (self.min[a] - ray.origin[a]) * inverse;
+            let mut t1 = (self.max[a] - ray.origin[a]) * inverse;
+            if t0 > t1 {
+                mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                t_enter = t0;
+                enter_axis = a;
+            }
+            if t1 < t_exit {
+                t_exit = t1;
+                exit_axis = a;
+            }
+        }
+
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let (t, axis, sign) =
+            if t_enter > t_min && t_enter < t_max {
+                (t_enter, enter_axis, -ray.direction[enter_axis].signum())
+            } else if t_exit > t_min && t_exit < t_max {
+                (t_exit, exit_axis, ray.direction[exit_axis].signum())
+            } else {
+                return None;
+            };
+
+        let point = ray.point_at_parameter(t);
+        let size = self.max - self.min;
+        let local = (point - self.min) / size;
+        // uv are the two other coordinates of the face, normalized
+        let (u, v) = match axis {
+            0 => (local.z, local.y),
+            1 => (local.x, local.z),
+            _ => (local.x, local.y),
+        };
+
+        let normal = axis_vector(axis, sign);
+        let tangent = axis_vector(if axis == 0 { 2 } else { 0 }, 1.);
+        Some(HitRecord {
+            t,
+            point,
+            normal,
+            tangent,
+            bitangent: bitangent_towards(normal, tangent, axis_vector(if axis == 1 { 2 } else { 1 }, 1.)),
+            u,
+            v,
+            material: &*self.material,
+        })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(
+            Aabb {
+                min: self.min,
+                max: self.max,
+            }
+        )
+    }
+}
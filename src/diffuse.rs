@@ -0,0 +1,164 @@
+// Matte surfaces that don't look like Lambertian's: rough ones like
+// concrete, clay or the moon, which reflect more light back towards where
+// it comes from, and cloth, whose fibers catch the light at grazing angles.
+
+use std::f32::consts::PI;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{HitRecord};
+use material::{Material, BsdfSample, BounceKind};
+use microfacet::{two_sided_frame};
+use texture::{Texture, ConstantTexture};
+use sampling::{self};
+
+// Oren and Nayar's model of a surface made of tiny lambertian V-shaped
+// grooves, `sigma` being the standard deviation of their slope angle, in
+// radians. It's lambertian for a `sigma` of 0, and flatter as it grows:
+// the full moon is as bright on its edges as in its middle.
+pub struct OrenNayar {
+    pub albedo: Box<dyn Texture>,
+    pub sigma: Box<dyn Texture>,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Box<dyn Texture>, sigma: f32) -> Self {
+        OrenNayar::textured(albedo, Box::new(ConstantTexture::fromf(sigma)))
+    }
+
+    pub fn textured(albedo: Box<dyn Texture>, sigma: Box<dyn Texture>) -> Self {
+        OrenNayar {
+            albedo,
+            sigma,
+        }
+    }
+
+    // brdf times cosine, in the local frame of the hit
+    fn reflectance(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z <= 0. || wi.z <= 0. {
+            return Vec3::zero();
+        }
+        let sigma2 = self.sigma.scalar(rec.u, rec.v, rec.point).powi(2);
+        let a = 1. - sigma2 / (2. * (sigma2 + 0.33));
+        let b = 0.45 * sigma2 / (sigma2 + 0.09);
+
+        // cosine of the azimuth between the two directions
+        let sin_o = (1. - wo.z * wo.z).max(0.).sqrt();
+        let sin_i = (1. - wi.z * wi.z).max(0.).sqrt();
+        let cos_phi =
+            if sin_o > 1e-4 && sin_i > 1e-4 { ((wo.x * wi.x + wo.y * wi.y) / (sin_o * sin_i)).max(0.) }
+            else { 0. };
+        // sine of the larger angle to the normal, tangent of the smaller one
+        let (sin_alpha, tan_beta) =
+            if wi.z < wo.z { (sin_i, sin_o / wo.z) } else { (sin_o, sin_i / wi.z) };
+
+        self.albedo.value(rec.u, rec.v, rec.point) * (a + b * cos_phi * sin_alpha * tan_beta) * wi.z / PI
+    }
+}
+
+impl Material for OrenNayar {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (frame, wo) = two_sided_frame(ray, rec);
+        let wi = sampling::cosine_hemisphere();
+        let pdf = sampling::cosine_hemisphere_pdf(wi.z);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            direction: frame.local(wi),
+            weight: self.reflectance(rec, wo, wi) / pdf,
+            pdf,
+            is_specular: false,
+            kind: BounceKind::Diffuse,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let (frame, wo) = two_sided_frame(ray, rec);
+        self.reflectance(rec, wo, frame.to_local(direction))
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let (frame, _) = two_sided_frame(ray, rec);
+        sampling::cosine_hemisphere_pdf(frame.to_local(direction).z)
+    }
+}
+
+// below it, the sheen reflects more light than it gets at grazing angles
+const MIN_SHEEN_ROUGHNESS: f32 = 0.1;
+
+// Estevez and Kulla's fit of the shadowing of their sheen, for the cosine
+// of a direction with the normal
+fn sheen_lambda(cos_theta: f32, roughness: f32) -> f32 {
+    let t = (1. - roughness).powi(2);
+    let mix = |smooth: f32, rough: f32| smooth * t + rough * (1. - t);
+    let (a, b, c) = (mix(25.3245, 21.5473), mix(3.32435, 3.82987), mix(0.16801, 0.19823));
+    let (d, e) = (mix(-1.27393, -1.97760), mix(-4.85967, -4.32054));
+    let l = |x: f32| a / (1. + b * x.powf(c)) + d * x + e;
+
+    if cos_theta < 0.5 { l(cos_theta).exp() } else { (2. * l(0.5) - l(1. - cos_theta)).exp() }
+}
+
+// The sheen of velvet and other cloth, after Estevez and Kulla's "Production
+// Friendly Microfacet Sheen BRDF": fibers standing out of the surface, which
+// light up near the silhouettes and leave the middle dark. It's usually
+// mixed with a diffuse material for the fabric underneath.
+pub struct Sheen {
+    pub color: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+}
+
+impl Sheen {
+    pub fn new(color: Box<dyn Texture>, roughness: f32) -> Self {
+        Sheen::textured(color, Box::new(ConstantTexture::fromf(roughness)))
+    }
+
+    pub fn textured(color: Box<dyn Texture>, roughness: Box<dyn Texture>) -> Self {
+        Sheen {
+            color,
+            roughness,
+        }
+    }
+
+    // brdf times cosine, in the local frame of the hit
+    fn reflectance(&self, rec: &HitRecord, wo: Vec3, wi: Vec3) -> Vec3 {
+        if wo.z <= 0. || wi.z <= 0. {
+            return Vec3::zero();
+        }
+        let roughness = self.roughness.scalar(rec.u, rec.v, rec.point).clamp(MIN_SHEEN_ROUGHNESS, 1.);
+        let m = (wo + wi).unit_vector();
+
+        // the fibers are mostly parallel to the surface
+        let sin_m = (1. - m.z * m.z).max(0.).sqrt();
+        let d = (2. + 1. / roughness) * sin_m.powf(1. / roughness) / (2. * PI);
+        let g = 1. / (1. + sheen_lambda(wo.z, roughness) + sheen_lambda(wi.z, roughness));
+
+        self.color.value(rec.u, rec.v, rec.point) * d * g / (4. * wo.z)
+    }
+}
+
+// The lobe is wide and peaks at grazing angles, where cosine weighted
+// samples would be scarce, so directions are picked uniformly.
+impl Material for Sheen {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (frame, wo) = two_sided_frame(ray, rec);
+        let wi = sampling::uniform_hemisphere();
+        let pdf = sampling::uniform_hemisphere_pdf();
+        Some(BsdfSample {
+            direction: frame.local(wi),
+            weight: self.reflectance(rec, wo, wi) / pdf,
+            pdf,
+            is_specular: false,
+            kind: BounceKind::Diffuse,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let (frame, wo) = two_sided_frame(ray, rec);
+        self.reflectance(rec, wo, frame.to_local(direction))
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let (frame, _) = two_sided_frame(ray, rec);
+        if frame.to_local(direction).z > 0. { sampling::uniform_hemisphere_pdf() } else { 0. }
+    }
+}
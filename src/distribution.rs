@@ -0,0 +1,102 @@
+// Piecewise constant distributions, to draw samples proportionally to a
+// tabulated function, like the brightness of an environment map.
+
+// Over [0, 1), each of the `func.len()` intervals being drawn with a
+// probability proportional to its value.
+pub struct Distribution1D {
+    func: Vec<f32>,
+    // cdf[i] is the probability of drawing before interval i
+    cdf: Vec<f32>,
+    // integral of the function over [0, 1)
+    integral: f32,
+}
+
+impl Distribution1D {
+    pub fn new(func: Vec<f32>) -> Self {
+        assert!(!func.is_empty(), "A distribution needs at least one value.");
+        let n = func.len();
+        let mut cdf = vec![0.; n + 1];
+        for i in 0..n {
+            cdf[i + 1] = cdf[i] + func[i].abs() / n as f32;
+        }
+        let integral = cdf[n];
+
+        // a function that's zero everywhere is sampled uniformly
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            *c = if integral > 0. { *c / integral } else { i as f32 / n as f32 };
+        }
+
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    pub fn integral(&self) -> f32 {
+        self.integral
+    }
+
+    // Turns a uniform random number into a sample in [0, 1). Returns the
+    // sample, its density, and the interval it fell in.
+    pub fn sample(&self, random: f32) -> (f32, f32, usize) {
+        // last entry of the cdf not above `random`, skipping the
+        // intervals that can't be drawn
+        let offset = (self.cdf.partition_point(|&c| c <= random).max(1) - 1).min(self.count() - 1);
+
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0. { (random - self.cdf[offset]) / width } else { 0. };
+
+        ((offset as f32 + du) / self.count() as f32, self.pdf_at(offset), offset)
+    }
+
+    fn pdf_at(&self, offset: usize) -> f32 {
+        if self.integral > 0. { self.func[offset].abs() / self.integral } else { 1. }
+    }
+
+    pub fn pdf(&self, x: f32) -> f32 {
+        let offset = ((x * self.count() as f32) as usize).min(self.count() - 1);
+        self.pdf_at(offset)
+    }
+}
+
+// Over [0, 1)², from a function tabulated on a grid of `width` by `height`
+// cells, row by row. A row is drawn from the marginal distribution, then a
+// column from the conditional distribution of that row.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f32], width: usize, height: usize) -> Self {
+        assert!(width > 0 && height > 0, "A distribution needs at least one cell.");
+        assert!(func.len() >= width * height, "The function has fewer values than cells.");
+        let conditional: Vec<Distribution1D> = func.chunks(width)
+            .take(height)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|row| row.integral()).collect());
+
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    // returns the sample as (u, v), u along the rows, and its density
+    pub fn sample(&self, random: (f32, f32)) -> ((f32, f32), f32) {
+        let (v, pdf_v, row) = self.marginal.sample(random.1);
+        let (u, pdf_u, _) = self.conditional[row].sample(random.0);
+        ((u, v), pdf_u * pdf_v)
+    }
+
+    pub fn pdf(&self, (u, v): (f32, f32)) -> f32 {
+        let row = ((v * self.conditional.len() as f32) as usize).min(self.conditional.len() - 1);
+        self.conditional[row].pdf(u) * self.marginal.pdf(v)
+    }
+}
@@ -0,0 +1,231 @@
+// Light coming from infinitely far away, all around the scene: skies and
+// environment maps. Directions are mapped to [0, 1)² with the usual
+// latitude-longitude layout, v going from the top of the sky (+y) down.
+
+extern crate rand;
+use self::rand::Rng;
+use std::f32;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use image::hdr::{HDRDecoder};
+use vec3::{Vec3};
+use light::{Light, LightSample};
+use distribution::{Distribution2D};
+
+pub trait Environment: Send + Sync {
+    // light arriving from `direction`, a unit vector
+    fn radiance(&self, direction: Vec3) -> Vec3;
+
+    // width and height of the grid the brightness is tabulated on, to be
+    // sampled as a light
+    fn resolution(&self) -> (usize, usize) {
+        (512, 256)
+    }
+}
+
+pub fn direction_to_uv(direction: Vec3) -> (f32, f32) {
+    let phi = direction.z.atan2(direction.x);
+    let theta = direction.y.clamp(-1., 1.).acos();
+    ((phi + PI) / (2. * PI), theta / PI)
+}
+
+pub fn uv_to_direction(u: f32, v: f32) -> Vec3 {
+    let phi = 2. * PI * u - PI;
+    let theta = PI * v;
+    Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin())
+}
+
+// An environment sampled proportionally to its brightness, tabulated on a
+// `width` by `height` grid. Bright and small features, like a sun, need
+// enough resolution to be found.
+pub struct EnvironmentLight {
+    pub environment: Arc<dyn Environment>,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentLight {
+    pub fn new(environment: Arc<dyn Environment>, width: usize, height: usize) -> Self {
+        let mut func = Vec::with_capacity(width * height);
+        for j in 0..height {
+            let v = (j as f32 + 0.5) / height as f32;
+            // the cells near the poles cover a smaller part of the sphere
+            let sin_theta = (PI * v).sin();
+            for i in 0..width {
+                let u = (i as f32 + 0.5) / width as f32;
+                func.push(environment.radiance(uv_to_direction(u, v)).luminance() * sin_theta);
+            }
+        }
+
+        EnvironmentLight {
+            environment,
+            distribution: Distribution2D::new(&func, width, height),
+        }
+    }
+}
+
+// converts a density over the [0, 1)² map to solid angle
+fn uv_pdf_to_solid_angle(pdf: f32, v: f32) -> f32 {
+    let sin_theta = (PI * v).sin();
+    if sin_theta <= 0. {
+        return 0.;
+    }
+    pdf / (2. * PI * PI * sin_theta)
+}
+
+impl Light for EnvironmentLight {
+    fn sample(&self, _point: Vec3, _time: f32) -> Option<LightSample> {
+        let mut rng = rand::thread_rng();
+        let ((u, v), pdf) = self.distribution.sample((rng.gen(), rng.gen()));
+        let pdf = uv_pdf_to_solid_angle(pdf, v);
+        if pdf <= 0. {
+            return None;
+        }
+
+        let direction = uv_to_direction(u, v);
+        Some(LightSample {
+            direction,
+            distance: f32::MAX,
+            radiance: self.environment.radiance(direction),
+            pdf,
+            is_delta: false,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, direction: Vec3) -> f32 {
+        let (u, v) = direction_to_uv(direction.unit_vector());
+        uv_pdf_to_solid_angle(self.distribution.pdf((u, v)), v)
+    }
+
+    fn is_environment(&self) -> bool {
+        true
+    }
+}
+
+// An equirectangular image, usually a high dynamic range probe. Texels are
+// looked up without filtering, so that the light samples them exactly
+// proportionally to their brightness.
+pub struct EnvironmentMap {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+    pub intensity: f32,
+}
+
+impl EnvironmentMap {
+    pub fn new(pixels: Vec<Vec3>, width: usize, height: usize, intensity: f32) -> Self {
+        EnvironmentMap {
+            width,
+            height,
+            pixels,
+            intensity,
+        }
+    }
+
+    // loads a Radiance .hdr file
+    pub fn from_hdr(path: &str, intensity: f32) -> Self {
+        let decoder = HDRDecoder::new(BufReader::new(File::open(path).unwrap())).unwrap();
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr().unwrap()
+            .iter()
+            .map(|pixel| Vec3::new(pixel[0], pixel[1], pixel[2]))
+            .collect();
+
+        EnvironmentMap::new(pixels, metadata.width as usize, metadata.height as usize, intensity)
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        let (u, v) = direction_to_uv(direction);
+        let i = ((u * self.width as f32) as usize).min(self.width - 1);
+        let j = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.pixels[j * self.width + i] * self.intensity
+    }
+
+    fn resolution(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a sky much brighter towards the top, with a small sun close to it
+    struct Sky;
+
+    impl Environment for Sky {
+        fn radiance(&self, direction: Vec3) -> Vec3 {
+            let sun = Vec3::new(0.3, 0.95, 0.).unit_vector();
+            let glow = if direction.dot(sun) > 0.99 { 50. } else { 0. };
+            Vec3::fromf(0.1 + direction.y.max(0.).powi(4) + glow)
+        }
+    }
+
+    fn sky_light() -> EnvironmentLight {
+        EnvironmentLight::new(Arc::new(Sky), 32, 16)
+    }
+
+    // integral of the pdf over the directions of [u0, u1) x [v0, v1), with
+    // the midpoint rule on a grid finer than the light's
+    fn integrate_pdf(light: &EnvironmentLight, (u0, u1): (f32, f32), (v0, v1): (f32, f32)) -> f64 {
+        let n = 64;
+        let (du, dv) = ((u1 - u0) / n as f32, (v1 - v0) / n as f32);
+        let mut integral = 0.;
+        for j in 0..n {
+            let v = v0 + (j as f32 + 0.5) * dv;
+            for i in 0..n {
+                let u = u0 + (i as f32 + 0.5) * du;
+                // the solid angle of the cell
+                let d_omega = 2. * PI * PI * (PI * v).sin() * du * dv;
+                integral += (light.pdf(Vec3::zero(), uv_to_direction(u, v)) * d_omega) as f64;
+            }
+        }
+        integral
+    }
+
+    #[test]
+    fn pdf_integrates_to_one() {
+        let light = sky_light();
+        let mut integral = 0.;
+        for j in 0..8 {
+            for i in 0..8 {
+                let (u, v) = (i as f32 / 8., j as f32 / 8.);
+                integral += integrate_pdf(&light, (u, u + 1. / 8.), (v, v + 1. / 8.));
+            }
+        }
+        assert!((integral - 1.).abs() < 1e-3, "the pdf integrates to {}", integral);
+    }
+
+    #[test]
+    fn samples_follow_the_pdf() {
+        let light = sky_light();
+        let samples = 200000;
+        let bins = 8;
+        let mut counts = vec![0; bins * bins];
+        for _ in 0..samples {
+            let sample = light.sample(Vec3::zero(), 0.).unwrap();
+            let (u, v) = direction_to_uv(sample.direction);
+            let i = ((u * bins as f32) as usize).min(bins - 1);
+            let j = ((v * bins as f32) as usize).min(bins - 1);
+            counts[j * bins + i] += 1;
+        }
+
+        // the top and bottom rows are the ones the sin theta term matters for
+        for j in 0..bins {
+            for i in 0..bins {
+                let (u, v) = (i as f32 / bins as f32, j as f32 / bins as f32);
+                let step = 1. / bins as f32;
+                let expected = integrate_pdf(&light, (u, u + step), (v, v + step));
+                let frequency = counts[j * bins + i] as f64 / samples as f64;
+                let tolerance = 5. * (expected * (1. - expected) / samples as f64).sqrt() + 1e-4;
+                assert!(
+                    (frequency - expected).abs() < tolerance,
+                    "bin ({}, {}) gets {} of the samples instead of {}", i, j, frequency, expected
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// Terrain defined by heights sampled on a regular grid in the xz plane.
+// Each grid cell is made of two triangles. To avoid testing every cell, we
+// keep a quadtree of the min and max heights of blocks of cells, and only go
+// down the blocks whose bounding box is hit by the ray.
+
+use std::sync::Arc;
+use std::f32;
+use std::path::Path;
+use image;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, tangent_towards, bitangent_towards, intersect_triangle};
+use material::{Material};
+use noises::{Perlin};
+
+// min and max heights of the blocks of one level of the quadtree
+struct MinMaxLevel {
+    width: usize,
+    depth: usize,
+    min: Vec<f32>,
+    max: Vec<f32>,
+}
+
+pub struct Heightfield {
+    // number of samples along x and z
+    nx: usize,
+    nz: usize,
+    // heights in [0, 1], row by row along x
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+    // level 0 has one block per cell, each next level merges 2x2 blocks
+    levels: Vec<MinMaxLevel>,
+    // corner of the terrain with the lowest coordinates
+    pub origin: Vec3,
+    // extent of the terrain along x and z, and height of a sample of 1
+    pub size: Vec3,
+    pub material: Arc<dyn Material>,
+}
+
+impl Heightfield {
+    // `heights` holds nx * nz samples in [0, 1], row by row along x
+    pub fn new(heights: Vec<f32>, nx: usize, nz: usize, origin: Vec3, size: Vec3, material: Arc<dyn Material>) -> Self {
+        assert!(nx >= 2 && nz >= 2, "A heightfield needs at least 2x2 samples.");
+        assert_eq!(heights.len(), nx * nz);
+
+        let mut heightfield = Heightfield {
+            nx,
+            nz,
+            heights,
+            normals: vec![],
+            levels: vec![],
+            origin,
+            size,
+            material,
+        };
+        heightfield.normals = heightfield.compute_normals();
+        heightfield.levels = heightfield.build_levels();
+        heightfield
+    }
+
+    // uses the luminance of a grayscale image as the heights
+    pub fn from_image(path: &str, origin: Vec3, size: Vec3, material: Arc<dyn Material>) -> Self {
+        let im = image::open(Path::new(path)).unwrap().to_luma();
+        let (width, height) = im.dimensions();
+        let heights = im.into_raw().iter().map(|h| *h as f32 / 255.).collect();
+        Heightfield::new(heights, width as usize, height as usize, origin, size, material)
+    }
+
+    // samples perlin turbulence over the terrain, `scale` being the
+    // frequency of the noise in world units
+    pub fn from_perlin(
+        noise: &Perlin,
+        scale: f32,
+        nx: usize,
+        nz: usize,
+        origin: Vec3,
+        size: Vec3,
+        material: Arc<dyn Material>,
+    ) -> Self {
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let x = origin.x + size.x * (i as f32) / ((nx - 1) as f32);
+                let z = origin.z + size.z * (j as f32) / ((nz - 1) as f32);
+                heights.push(noise.turb(Vec3::new(x, 0., z) * scale).min(1.));
+            }
+        }
+        Heightfield::new(heights, nx, nz, origin, size, material)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * self.nx + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        self.origin + Vec3::new(
+            self.size.x * (i as f32) / ((self.nx - 1) as f32),
+            self.size.y * self.height(i, j),
+            self.size.z * (j as f32) / ((self.nz - 1) as f32),
+        )
+    }
+
+    fn compute_normals(&self) -> Vec<Vec3> {
+        let mut normals = Vec::with_capacity(self.nx * self.nz);
+        for j in 0..self.nz {
+            for i in 0..self.nx {
+                // central differences, one sided on the borders
+                let left = self.vertex(i.saturating_sub(1), j);
+                let right = self.vertex((i + 1).min(self.nx - 1), j);
+                let back = self.vertex(i, j.saturating_sub(1));
+                let front = self.vertex(i, (j + 1).min(self.nz - 1));
+                normals.push((front - back).cross(right - left).unit_vector());
+            }
+        }
+        normals
+    }
+
+    fn build_levels(&self) -> Vec<MinMaxLevel> {
+        let width = self.nx - 1;
+        let depth = self.nz - 1;
+        let mut min = Vec::with_capacity(width * depth);
+        let mut max = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let corners = [
+                    self.height(i, j),
+                    self.height(i + 1, j),
+                    self.height(i, j + 1),
+                    self.height(i + 1, j + 1),
+                ];
+                min.push(corners.iter().cloned().fold(f32::INFINITY, f32::min));
+                max.push(corners.iter().cloned().fold(f32::NEG_INFINITY, f32::max));
+            }
+        }
+
+        let mut levels = vec![MinMaxLevel { width, depth, min, max }];
+
+        loop {
+            let next = {
+                let last = &levels[levels.len() - 1];
+                if last.width == 1 && last.depth == 1 {
+                    break;
+                }
+                let width = last.width.div_ceil(2);
+                let depth = last.depth.div_ceil(2);
+                let mut min = vec![f32::INFINITY; width * depth];
+                let mut max = vec![f32::NEG_INFINITY; width * depth];
+                for j in 0..last.depth {
+                    for i in 0..last.width {
+                        let parent = (j / 2) * width + i / 2;
+                        min[parent] = min[parent].min(last.min[j * last.width + i]);
+                        max[parent] = max[parent].max(last.max[j * last.width + i]);
+                    }
+                }
+                MinMaxLevel { width, depth, min, max }
+            };
+            levels.push(next);
+        }
+
+        levels
+    }
+
+    fn block_box(&self, level: usize, i: usize, j: usize) -> Aabb {
+        let cells = 1 << level;
+        let cell_x = self.size.x / ((self.nx - 1) as f32);
+        let cell_z = self.size.z / ((self.nz - 1) as f32);
+        let index = j * self.levels[level].width + i;
+        let x0 = (i * cells) as f32 * cell_x;
+        let z0 = (j * cells) as f32 * cell_z;
+        let x1 = (((i + 1) * cells).min(self.nx - 1)) as f32 * cell_x;
+        let z1 = (((j + 1) * cells).min(self.nz - 1)) as f32 * cell_z;
+        Aabb {
+            min: self.origin + Vec3::new(x0, self.size.y * self.levels[level].min[index] - 0.0001, z0),
+            max: self.origin + Vec3::new(x1, self.size.y * self.levels[level].max[index] + 0.0001, z1),
+        }
+    }
+
+    // closest hit in the cell (i, j): t, point and normal
+    fn hit_cell(&self, r: &Ray, i: usize, j: usize, t_min: f32, t_max: f32) -> Option<(f32, Vec3, Vec3)> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let p: Vec<Vec3> = corners.iter().map(|&(i, j)| self.vertex(i, j)).collect();
+        let n: Vec<Vec3> = corners.iter().map(|&(i, j)| self.normals[j * self.nx + i]).collect();
+
+        let mut closest = None;
+        let mut t_max = t_max;
+        for &(a, b, c) in [(0, 1, 2), (0, 2, 3)].iter() {
+            if let Some((t, b1, b2)) = intersect_triangle(r, p[a], p[b], p[c], t_min, t_max) {
+                t_max = t;
+                let normal = (n[a] * (1. - b1 - b2) + n[b] * b1 + n[c] * b2).unit_vector();
+                closest = Some((t, r.point_at_parameter(t), normal));
+            }
+        }
+        closest
+    }
+
+    fn hit_block(&self, r: &Ray, level: usize, i: usize, j: usize, t_min: f32, t_max: f32) -> Option<(f32, Vec3, Vec3)> {
+        if !self.block_box(level, i, j).hit(r, t_min, t_max) {
+            return None;
+        }
+
+        if level == 0 {
+            return self.hit_cell(r, i, j, t_min, t_max);
+        }
+
+        let children = &self.levels[level - 1];
+        let mut closest = None;
+        let mut t_max = t_max;
+        for &(ci, cj) in [(2 * i, 2 * j), (2 * i + 1, 2 * j), (2 * i, 2 * j + 1), (2 * i + 1, 2 * j + 1)].iter() {
+            if ci >= children.width || cj >= children.depth {
+                continue;
+            }
+            if let Some(hit) = self.hit_block(r, level - 1, ci, cj, t_min, t_max) {
+                t_max = hit.0;
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+}
+
+impl Hitable for Heightfield {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let root = self.levels.len() - 1;
+        self.hit_block(r, root, 0, 0, t_min, t_max)
+            .map(|(t, point, normal)| {
+                let tangent = tangent_towards(normal, Vec3::new(1., 0., 0.));
+                HitRecord {
+                    t,
+                    point,
+                    normal,
+                    tangent,
+                    bitangent: bitangent_towards(normal, tangent, Vec3::new(0., 0., 1.)),
+                    u: (point.x - self.origin.x) / self.size.x,
+                    v: (point.z - self.origin.z) / self.size.z,
+                    material: &*self.material,
+                }
+            })
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(self.block_box(self.levels.len() - 1, 0, 0))
+    }
+}
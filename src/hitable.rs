@@ -1,26 +1,104 @@
 use std::sync::Arc;
+use std::f32;
+use std::f32::consts::PI;
 use vec3::{Vec3};
 use ray::{Ray};
 use aabb::{Aabb};
 use material::{Material};
+use sampling::{self, Onb};
 
+#[derive(Clone, Copy)]
 pub struct HitRecord<'a> {
     pub t: f32,
     pub point: Vec3,
     pub normal: Vec3,
+    // unit vector along the surface, usually where u increases, for
+    // tangent space normal maps and anisotropic materials
+    pub tangent: Vec3,
+    // unit vector along the surface orthogonal to the tangent, usually where
+    // v increases, so the frame can be of either handedness
+    pub bitangent: Vec3,
+    pub u: f32,
+    pub v: f32,
     pub material: &'a dyn Material,
 }
 
-pub trait Hitable {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord>;
+// The unit vector of the tangent plane of `normal` closest to `direction`,
+// or any of them when `direction` is along the normal.
+pub fn tangent_towards(normal: Vec3, direction: Vec3) -> Vec3 {
+    Onb::from_wu(normal, direction).u
+}
+
+// The unit vector of the tangent plane of `normal` orthogonal to `tangent`,
+// on the side of `direction`.
+pub fn bitangent_towards(normal: Vec3, tangent: Vec3, direction: Vec3) -> Vec3 {
+    let bitangent = normal.cross(tangent);
+    if bitangent.dot(direction) < 0. { -bitangent } else { bitangent }
+}
+
+// Distance along the ray to the triangle, strictly between t_min and t_max,
+// and the barycentric coordinates of p1 and p2 at the hit (Möller and
+// Trumbore).
+pub fn intersect_triangle(r: &Ray, p0: Vec3, p1: Vec3, p2: Vec3, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge1 = p1 - p0;
+    let edge2 = p2 - p0;
+    let pvec = r.direction.cross(edge2);
+    let determinant = edge1.dot(pvec);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1. / determinant;
+    let tvec = r.origin - p0;
+    let b1 = tvec.dot(pvec) * inverse;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(edge1);
+    let b2 = r.direction.dot(qvec) * inverse;
+    if b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+    let t = edge2.dot(qvec) * inverse;
+    if t > t_min && t < t_max {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+pub trait Hitable: Send + Sync {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>>;
     fn bounding_box(&self, t0: f32, t1: f32) ->
         Option<Aabb>;
+
+    // Shapes that can be used as lights implement these two to sample
+    // points on their surface as seen from `origin`, and say so here.
+    fn is_samplable(&self) -> bool {
+        false
+    }
+
+    // density, in solid angle, of `random` returning `direction`
+    fn pdf_value(&self, _origin: Vec3, _direction: Vec3) -> f32 {
+        0.
+    }
+
+    // direction from `origin` towards a random point of the surface
+    fn random(&self, _origin: Vec3) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
+
+    // Fraction of the light going through along the ray between t_min and
+    // t_max, for shadow rays. Surfaces block everything, media let some of
+    // it through.
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        if self.hit(r, t_min, t_max).is_some() { 0. } else { 1. }
+    }
 }
 
 pub type HitableList = Vec<Box<dyn Hitable>>;
 
 impl Hitable for HitableList {
-    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         self.iter().fold(None, |acc, item| {
             match (acc, item.hit(r, t_min, t_max)) {
                 (None, None) => None,
@@ -37,6 +115,17 @@ impl Hitable for HitableList {
         })
     }
 
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        let mut transmittance = 1.;
+        for item in self.iter() {
+            transmittance *= item.transmittance(r, t_min, t_max);
+            if transmittance == 0. {
+                break;
+            }
+        }
+        transmittance
+    }
+
     fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
         self.iter().fold(None, |acc, item| {
             match (acc, item.bounding_box(t0, t1)) {
@@ -49,6 +138,28 @@ impl Hitable for HitableList {
     }
 }
 
+pub fn get_sphere_uv(p: &Vec3) -> (f32, f32) {
+    // phi "left-right" angle of the point on the sphere
+    let phi =  p.z.atan2(p.x);
+    // theta is the up-down angle of the point on the sphere
+    let theta = p.y.asin();
+
+    (
+        1. - (phi + PI) / (2. * PI),
+        (theta + PI / 2.) / PI
+    )
+}
+
+// u increases westwards, towards -phi
+pub fn sphere_tangent(normal: Vec3) -> Vec3 {
+    tangent_towards(normal, Vec3::new(normal.z, 0., -normal.x))
+}
+
+// v increases northwards, towards +y
+pub fn sphere_bitangent(normal: Vec3, tangent: Vec3) -> Vec3 {
+    bitangent_towards(normal, tangent, Vec3::new(0., 1., 0.))
+}
+
 fn hit_sphere<'a>(
     center: Vec3,
     radius: f32,
@@ -68,10 +179,17 @@ fn hit_sphere<'a>(
 
         if t1 < t_max && t1 > t_min {
             let point = ray.point_at_parameter(t1);
+            let normal = (point - center) / radius;
+            let (u, v) = get_sphere_uv(&normal);
+            let tangent = sphere_tangent(normal);
             return Some(HitRecord {
                 t: t1,
                 point,
-                normal: (point - center) / radius,
+                normal,
+                tangent,
+                bitangent: sphere_bitangent(normal, tangent),
+                u,
+                v,
                 material,
             });
         }
@@ -80,10 +198,17 @@ fn hit_sphere<'a>(
 
         if t2 < t_max && t2 > t_min {
             let point = ray.point_at_parameter(t2);
+            let normal = (point - center) / radius;
+            let (u, v) = get_sphere_uv(&normal);
+            let tangent = sphere_tangent(normal);
             return Some(HitRecord {
                 t: t2,
                 point,
-                normal: (point - center) / radius,
+                normal,
+                tangent,
+                bitangent: sphere_bitangent(normal, tangent),
+                u,
+                v,
                 material,
             });
         }
@@ -106,10 +231,21 @@ impl Sphere {
             material,
         }
     }
+
+    // cosine of the half angle of the cone the sphere covers seen from
+    // `origin`, None when `origin` is inside the sphere
+    fn cos_theta_max(&self, origin: Vec3) -> Option<f32> {
+        let distance_squared = (self.center - origin).squared_length();
+        if distance_squared <= self.radius.powi(2) {
+            None
+        } else {
+            Some((1. - self.radius.powi(2) / distance_squared).sqrt())
+        }
+    }
 }
 
 impl Hitable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         hit_sphere(
             self.center,
             self.radius,
@@ -128,6 +264,31 @@ impl Hitable for Sphere {
             }
         )
     }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let ray = Ray { origin, direction, time: 0. };
+        if self.hit(&ray, 0.001, f32::MAX).is_none() {
+            return 0.;
+        }
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) => sampling::uniform_cone_pdf(cos_theta_max),
+            None => sampling::uniform_sphere_pdf(),
+        }
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        // the sphere is seen as a cone of directions from the outside,
+        // and covers them all from the inside
+        match self.cos_theta_max(origin) {
+            Some(cos_theta_max) =>
+                Onb::from_w(self.center - origin).local(sampling::uniform_cone(cos_theta_max)),
+            None => sampling::uniform_sphere(),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -158,7 +319,7 @@ impl MovingSphere {
 }
 
 impl Hitable for MovingSphere {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
         hit_sphere(
             self.center(ray.time),
             self.radius,
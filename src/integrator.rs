@@ -0,0 +1,694 @@
+// The rendering algorithms: how much light arrives at the camera along a ray.
+
+extern crate rand;
+use self::rand::Rng;
+use std::f32;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{HitRecord};
+use material::{Material, BounceKind};
+use scene::{Scene};
+use mis::{Heuristic};
+use sampling::{self, Onb};
+
+pub trait Integrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3;
+}
+
+const T_MIN: f32 = 0.01;
+const T_MAX: f32 = f32::MAX;
+
+// light arriving at the hit point straight from one of the scene's lights
+fn direct_lighting(r: &Ray, rec: &HitRecord, scene: &Scene) -> Vec3 {
+    match scene.sample_light(rec.point, r.time) {
+        Some(light) => {
+            let transmittance = scene.transmittance(rec.point, light.direction, light.distance, r.time);
+            if transmittance == 0. {
+                return Vec3::fromf(0.);
+            }
+            rec.material.eval(r, rec, light.direction) * light.radiance * transmittance / light.pdf
+        },
+        None => Vec3::fromf(0.),
+    }
+}
+
+// When paths stop. Each kind of bounce has its own limit, so that glass
+// can be followed much deeper than diffuse interreflections. Past
+// `roulette_depth` bounces, paths carrying little light are randomly
+// killed, and the survivors are boosted to make up for them.
+#[derive(Debug, Copy, Clone)]
+pub struct PathTermination {
+    pub max_diffuse: u32,
+    pub max_specular: u32,
+    pub max_transmission: u32,
+    pub max_volume: u32,
+    pub roulette_depth: u32,
+}
+
+impl Default for PathTermination {
+    fn default() -> Self {
+        PathTermination {
+            max_diffuse: 8,
+            max_specular: 32,
+            max_transmission: 32,
+            max_volume: 64,
+            roulette_depth: 3,
+        }
+    }
+}
+
+// the bounces made so far by a path
+#[derive(Default)]
+struct Bounces {
+    diffuse: u32,
+    specular: u32,
+    transmission: u32,
+    volume: u32,
+}
+
+impl PathTermination {
+    // Records a bounce of `kind` and tells whether the path goes on after
+    // it, scaling `throughput` up if it survived the roulette.
+    fn continue_path(&self, bounces: &mut Bounces, kind: BounceKind, throughput: &mut Vec3) -> bool {
+        let (count, max) = match kind {
+            BounceKind::Diffuse => (&mut bounces.diffuse, self.max_diffuse),
+            BounceKind::Specular => (&mut bounces.specular, self.max_specular),
+            BounceKind::Transmission => (&mut bounces.transmission, self.max_transmission),
+            BounceKind::Volume => (&mut bounces.volume, self.max_volume),
+        };
+        if *count >= max {
+            return false;
+        }
+        *count += 1;
+
+        if bounces.diffuse + bounces.specular + bounces.transmission + bounces.volume <= self.roulette_depth {
+            return true;
+        }
+        let survival = throughput.x.max(throughput.y).max(throughput.z).min(1.);
+        if survival <= 0. || rand::thread_rng().gen::<f32>() >= survival {
+            return false;
+        }
+        *throughput = *throughput / survival;
+        true
+    }
+}
+
+// Follows the bounces picked by the materials until a ray escapes the
+// scene or hits a light. Simple, but very noisy with small lights.
+pub struct PathIntegrator {
+    pub termination: PathTermination,
+}
+
+impl PathIntegrator {
+    pub fn new(termination: PathTermination) -> Self {
+        PathIntegrator {
+            termination,
+        }
+    }
+}
+
+impl Integrator for PathIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        let mut radiance = Vec3::fromf(0.);
+        let mut throughput = Vec3::fromf(1.);
+        let mut ray = *r;
+        let mut bounces = Bounces::default();
+
+        loop {
+            let rec = match scene.hit(&ray, T_MIN, T_MAX) {
+                Some(rec) => rec,
+                None => {
+                    radiance = radiance + throughput * (scene.background)(&ray);
+                    break;
+                },
+            };
+
+            radiance = radiance + throughput * rec.material.emitted(&ray, &rec);
+
+            let bsdf = match rec.material.sample(&ray, &rec) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+
+            throughput = throughput * bsdf.weight;
+            if !self.termination.continue_path(&mut bounces, bsdf.kind, &mut throughput) {
+                break;
+            }
+            ray = Ray {
+                origin: rec.point,
+                direction: bsdf.direction,
+                time: ray.time,
+            };
+        }
+
+        radiance
+    }
+}
+
+// Path tracing with next event estimation: at each bounce on a non specular
+// surface, a light is sampled and its contribution added if it's visible.
+// The emission of the lights found by the next bounce is then skipped, or it
+// would be counted twice. It is only counted after specular bounces, for
+// which lights can't be sampled. The same goes for the background when it's
+// sampled by an environment light. Emitters that aren't lights, like a fire,
+// are always counted.
+pub struct NeePathIntegrator {
+    pub termination: PathTermination,
+}
+
+impl NeePathIntegrator {
+    pub fn new(termination: PathTermination) -> Self {
+        NeePathIntegrator {
+            termination,
+        }
+    }
+}
+
+impl Integrator for NeePathIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        let mut radiance = Vec3::fromf(0.);
+        let mut throughput = Vec3::fromf(1.);
+        let mut ray = *r;
+        let mut bounces = Bounces::default();
+        let mut count_emitted = true;
+
+        loop {
+            let rec = match scene.hit(&ray, T_MIN, T_MAX) {
+                Some(rec) => rec,
+                None => {
+                    if count_emitted || !scene.has_environment_light() {
+                        radiance = radiance + throughput * (scene.background)(&ray);
+                    }
+                    break;
+                },
+            };
+
+            let emitted = rec.material.emitted(&ray, &rec);
+            if emitted != Vec3::zero() && (count_emitted || !scene.is_light(&ray, &rec)) {
+                radiance = radiance + throughput * emitted;
+            }
+
+            // the lights are sampled even when the bsdf sample fails, which
+            // happens to rough surfaces reflecting below the horizon
+            if !rec.material.is_specular(&rec) {
+                radiance = radiance + throughput * direct_lighting(&ray, &rec, scene);
+            }
+
+            let bsdf = match rec.material.sample(&ray, &rec) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+
+            throughput = throughput * bsdf.weight;
+            if !self.termination.continue_path(&mut bounces, bsdf.kind, &mut throughput) {
+                break;
+            }
+            count_emitted = bsdf.is_specular;
+            ray = Ray {
+                origin: rec.point,
+                direction: bsdf.direction,
+                time: ray.time,
+            };
+        }
+
+        radiance
+    }
+}
+
+// Path tracing combining bsdf sampling and light sampling with multiple
+// importance sampling. Emission hit by a bsdf sample is weighted against
+// the chance of sampling it from the lights, and the other way around.
+pub struct MisPathIntegrator {
+    pub heuristic: Heuristic,
+    pub termination: PathTermination,
+}
+
+impl MisPathIntegrator {
+    pub fn new(heuristic: Heuristic, termination: PathTermination) -> Self {
+        MisPathIntegrator {
+            heuristic,
+            termination,
+        }
+    }
+}
+
+impl Integrator for MisPathIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        let mut radiance = Vec3::fromf(0.);
+        let mut throughput = Vec3::fromf(1.);
+        let mut ray = *r;
+        let mut bounces = Bounces::default();
+        // density of the bsdf sample that gave `ray`,
+        // None for camera rays and specular bounces
+        let mut bsdf_pdf: Option<f32> = None;
+
+        loop {
+            let rec = match scene.hit(&ray, T_MIN, T_MAX) {
+                Some(rec) => rec,
+                None => {
+                    let weight = match bsdf_pdf {
+                        Some(pdf) => {
+                            let light_pdf = scene.light_pdf(ray.origin, ray.direction.unit_vector(), true);
+                            self.heuristic.weight(pdf, light_pdf)
+                        },
+                        None => 1.,
+                    };
+                    radiance = radiance + throughput * (scene.background)(&ray) * weight;
+                    break;
+                },
+            };
+
+            let emitted = rec.material.emitted(&ray, &rec);
+            if emitted != Vec3::fromf(0.) {
+                let weight = match bsdf_pdf {
+                    // emitters that aren't lights are only found this way
+                    Some(pdf) if scene.is_light(&ray, &rec) => {
+                        let light_pdf = scene.light_pdf(ray.origin, ray.direction.unit_vector(), false);
+                        self.heuristic.weight(pdf, light_pdf)
+                    },
+                    _ => 1.,
+                };
+                radiance = radiance + throughput * emitted * weight;
+            }
+
+            if !rec.material.is_specular(&rec) {
+                if let Some(light) = scene.sample_light(rec.point, ray.time) {
+                    let transmittance = scene.transmittance(rec.point, light.direction, light.distance, ray.time);
+                    if transmittance > 0. {
+                        let f = rec.material.eval(&ray, &rec, light.direction) * transmittance;
+                        let weight =
+                            if light.is_delta { 1. }
+                            else { self.heuristic.weight(light.pdf, rec.material.pdf(&ray, &rec, light.direction)) };
+                        radiance = radiance + throughput * f * light.radiance * weight / light.pdf;
+                    }
+                }
+            }
+
+            let bsdf = match rec.material.sample(&ray, &rec) {
+                Some(bsdf) => bsdf,
+                None => break,
+            };
+
+            throughput = throughput * bsdf.weight;
+            if !self.termination.continue_path(&mut bounces, bsdf.kind, &mut throughput) {
+                break;
+            }
+            bsdf_pdf = if bsdf.is_specular { None } else { Some(bsdf.pdf) };
+            ray = Ray {
+                origin: rec.point,
+                direction: bsdf.direction,
+                time: ray.time,
+            };
+        }
+
+        radiance
+    }
+}
+
+// Only the light coming straight from the lights, plus what's seen through
+// mirrors and glass.
+pub struct DirectLightingIntegrator;
+
+impl DirectLightingIntegrator {
+    fn color(&self, r: &Ray, scene: &Scene, depth: u64) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => {
+                let emitted = rec.material.emitted(r, &rec);
+                if !rec.material.is_specular(&rec) {
+                    return emitted + direct_lighting(r, &rec, scene);
+                }
+                match (depth < 5, rec.material.scatter(r, &rec)) {
+                    (true, Some(mat_rec)) =>
+                        emitted + mat_rec.attenuation * self.color(&mat_rec.scattered, scene, depth + 1),
+                    _ => emitted,
+                }
+            },
+            None => (scene.background)(r),
+        }
+    }
+}
+
+impl Integrator for DirectLightingIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        self.color(r, scene, 0)
+    }
+}
+
+// White where the hemisphere above the surface is open up to `distance`,
+// darker in corners and creases.
+pub struct AmbientOcclusionIntegrator {
+    pub distance: f32,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(distance: f32) -> Self {
+        AmbientOcclusionIntegrator {
+            distance,
+        }
+    }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => {
+                // face the normal towards the viewer, and sample the
+                // cosine weighted hemisphere around it
+                let normal =
+                    if rec.normal.dot(r.direction) > 0. { -rec.normal }
+                    else { rec.normal };
+                let direction = Onb::from_w(normal).local(sampling::cosine_hemisphere());
+                if scene.occluded(rec.point, direction, self.distance, r.time) {
+                    Vec3::fromf(0.)
+                } else {
+                    Vec3::fromf(1.)
+                }
+            },
+            None => Vec3::fromf(1.),
+        }
+    }
+}
+
+// Classic recursive ray tracing: direct light on diffuse surfaces, and
+// perfect reflections and refractions on specular ones. There's no
+// indirect diffuse light at all.
+pub struct WhittedIntegrator {
+    pub max_depth: u64,
+}
+
+impl WhittedIntegrator {
+    pub fn new(max_depth: u64) -> Self {
+        WhittedIntegrator {
+            max_depth,
+        }
+    }
+
+    fn color(&self, r: &Ray, scene: &Scene, depth: u64) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => {
+                let emitted = rec.material.emitted(r, &rec);
+
+                // one shadow ray per light
+                let direct = scene.lights.iter().fold(Vec3::fromf(0.), |acc, light| {
+                    match light.sample(rec.point, r.time) {
+                        Some(sample) => {
+                            let transmittance = scene.transmittance(rec.point, sample.direction, sample.distance, r.time);
+                            acc + rec.material.eval(r, &rec, sample.direction) * sample.radiance * transmittance / sample.pdf
+                        },
+                        None => acc,
+                    }
+                });
+
+                let reflected = match (depth < self.max_depth, rec.material.sample(r, &rec)) {
+                    (true, Some(bsdf)) if bsdf.is_specular => {
+                        let scattered = Ray {
+                            origin: rec.point,
+                            direction: bsdf.direction,
+                            time: r.time,
+                        };
+                        bsdf.weight * self.color(&scattered, scene, depth + 1)
+                    },
+                    _ => Vec3::fromf(0.),
+                };
+
+                emitted + direct + reflected
+            },
+            None => (scene.background)(r),
+        }
+    }
+}
+
+impl Integrator for WhittedIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        self.color(r, scene, 0)
+    }
+}
+
+// Debug integrators, to check the geometry of a scene without noise.
+
+// normals mapped from [-1, 1] to [0, 1]
+pub struct NormalsIntegrator;
+
+impl Integrator for NormalsIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => (rec.normal + 1.) * 0.5,
+            None => Vec3::fromf(0.),
+        }
+    }
+}
+
+// distance to the camera, white being `max_distance` or farther
+pub struct DepthIntegrator {
+    pub max_distance: f32,
+}
+
+impl DepthIntegrator {
+    pub fn new(max_distance: f32) -> Self {
+        DepthIntegrator {
+            max_distance,
+        }
+    }
+}
+
+impl Integrator for DepthIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => {
+                let distance = rec.t * r.direction.length();
+                Vec3::fromf((distance / self.max_distance).min(1.))
+            },
+            None => Vec3::fromf(1.),
+        }
+    }
+}
+
+// texture coordinates, u in red and v in green
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => Vec3::new(rec.u, rec.v, 0.),
+            None => Vec3::fromf(0.),
+        }
+    }
+}
+
+// a random looking color per material instance
+pub struct MaterialIdIntegrator;
+
+impl Integrator for MaterialIdIntegrator {
+    fn li(&self, r: &Ray, scene: &Scene) -> Vec3 {
+        match scene.hit(r, T_MIN, T_MAX) {
+            Some(rec) => {
+                let address = rec.material as *const dyn Material as *const () as usize as u64;
+                // scrambles the bits of the address, so that materials
+                // allocated next to each other get different colors
+                let mut hash = address.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+                hash ^= hash >> 29;
+                Vec3::new(
+                    (hash & 0xff) as f32 / 255.,
+                    ((hash >> 8) & 0xff) as f32 / 255.,
+                    ((hash >> 16) & 0xff) as f32 / 255.,
+                )
+            },
+            None => Vec3::fromf(0.),
+        }
+    }
+}
+
+type IntegratorConstructor = fn(PathTermination) -> Box<dyn Integrator>;
+
+// the integrators, by their name on the command line
+const INTEGRATORS: [(&str, IntegratorConstructor); 11] = [
+    ("path", |termination| Box::new(PathIntegrator::new(termination))),
+    ("nee", |termination| Box::new(NeePathIntegrator::new(termination))),
+    ("mis", |termination| Box::new(MisPathIntegrator::new(Heuristic::Power, termination))),
+    ("direct", |_| Box::new(DirectLightingIntegrator)),
+    ("ao", |_| Box::new(AmbientOcclusionIntegrator::new(1.))),
+    ("whitted", |_| Box::new(WhittedIntegrator::new(5))),
+    ("normals", |_| Box::new(NormalsIntegrator)),
+    ("depth", |_| Box::new(DepthIntegrator::new(20.))),
+    ("uv", |_| Box::new(UvIntegrator)),
+    ("material", |_| Box::new(MaterialIdIntegrator)),
+    ("mis-balance", |termination| Box::new(MisPathIntegrator::new(Heuristic::Balance, termination))),
+];
+
+pub fn integrator_names() -> Vec<&'static str> {
+    INTEGRATORS.iter().map(|&(name, _)| name).collect()
+}
+
+pub fn integrator_from_name(name: &str, termination: PathTermination) -> Option<Box<dyn Integrator>> {
+    INTEGRATORS.iter().find(|&&(n, _)| n == name).map(|&(_, new)| new(termination))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use hitable::{Hitable, Sphere};
+    use material::{Lambertian, DiffuseLight, Isotropic};
+    use medium::{HeterogeneousMedium, DensityField};
+    use quadrics::{Disk, Annulus};
+    use texture::{ConstantTexture};
+    use rect::{XzRect};
+    use light::{Light, AreaLight};
+    use bvh_node::{BvhTree};
+
+    const ALBEDO: f32 = 0.5;
+    const RADIANCE: f32 = 4.;
+    const RADIUS: f32 = 0.5;
+    const HEIGHT: f32 = 2.;
+
+    // Estimates the light leaving a lambertian plane under a spherical
+    // light, and checks it against the analytic value. A sphere whose radius
+    // is seen under the angle a gives an irradiance of pi * L * sin^2(a),
+    // and the plane reflects albedo / pi of it in every direction. With a
+    // `side` of -1 the light and the viewer are below the plane, behind its
+    // normal.
+    fn assert_converges(name: &str, side: f32) {
+        let light = Sphere::new(Vec3::new(0., side * HEIGHT, 0.), RADIUS, Arc::new(
+            DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(RADIANCE))))
+        ));
+        let mut world: Vec<Box<dyn Hitable>> = vec![
+            Box::new(XzRect::new(-100., 100., -100., 100., 0., Arc::new(
+                Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(ALBEDO))))
+            ))),
+            Box::new(light.clone()),
+        ];
+        let lights: Vec<Box<dyn Light>> = vec![Box::new(AreaLight::new(Box::new(light)))];
+        let scene = Scene::new(BvhTree::new(&mut world, 0., 1.), lights, Box::new(|_: &Ray| Vec3::zero()));
+
+        // looking at the point right under the light
+        let r = Ray { origin: Vec3::new(3., side * 0.5, 0.), direction: Vec3::new(-3., -side * 0.5, 0.), time: 0. };
+        let expected = (ALBEDO * RADIANCE * (RADIUS / HEIGHT).powi(2)) as f64;
+        let (mean, standard_error) = estimate(name, &scene, &r);
+
+        assert!(
+            (mean - expected).abs() < 5. * standard_error + 1e-4,
+            "{} gives {} +- {} instead of {}", name, mean, standard_error, expected
+        );
+        // and it did converge close enough for the check to mean something
+        assert!(standard_error < 0.05 * expected, "{} is too noisy: {} +- {}", name, mean, standard_error);
+    }
+
+    // mean and standard error of the red light `name` finds along `r`
+    fn estimate(name: &str, scene: &Scene, r: &Ray) -> (f64, f64) {
+        let integrator = integrator_from_name(name, PathTermination::default()).unwrap();
+        let samples = 50000;
+        let values: Vec<f64> = (0..samples).map(|_| integrator.li(r, scene).x as f64).collect();
+        let mean = values.iter().sum::<f64>() / samples as f64;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples - 1) as f64;
+        (mean, (variance / samples as f64).sqrt())
+    }
+
+    struct Uniform(f32);
+
+    impl DensityField for Uniform {
+        fn density(&self, _p: Vec3) -> f32 {
+            self.0
+        }
+
+        fn max_density(&self) -> f32 {
+            self.0
+        }
+    }
+
+    // A glowing medium next to the light, seen only through the plane. It
+    // isn't one of the lights, so the integrators that sample them have to
+    // count its emission when a bounce finds it, like the path integrator.
+    #[test]
+    fn emissive_media_converge() {
+        let light = Sphere::new(Vec3::new(0., HEIGHT, 0.), RADIUS, Arc::new(
+            DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(RADIANCE))))
+        ));
+        let fire = HeterogeneousMedium::emissive(
+            Box::new(Sphere::new(Vec3::new(0., 0.8, 1.5), 0.5, Arc::new(Lambertian::new(Box::new(ConstantTexture::fromf(0.)))))),
+            Box::new(Uniform(2.)),
+            Arc::new(Isotropic::new(Box::new(ConstantTexture::fromf(0.5)))),
+            Box::new(Uniform(1.)),
+            Vec3::fromf(8.),
+        );
+        let mut world: Vec<Box<dyn Hitable>> = vec![
+            Box::new(XzRect::new(-100., 100., -100., 100., 0., Arc::new(
+                Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(ALBEDO))))
+            ))),
+            Box::new(light.clone()),
+            Box::new(fire),
+        ];
+        let lights: Vec<Box<dyn Light>> = vec![Box::new(AreaLight::new(Box::new(light)))];
+        let scene = Scene::new(BvhTree::new(&mut world, 0., 1.), lights, Box::new(|_: &Ray| Vec3::zero()));
+
+        let r = Ray { origin: Vec3::new(3., 0.5, 0.), direction: Vec3::new(-3., -0.5, 0.), time: 0. };
+        let (expected, expected_error) = estimate("path", &scene, &r);
+        for name in ["nee", "mis"].iter() {
+            let (mean, standard_error) = estimate(name, &scene, &r);
+            let error = (standard_error.powi(2) + expected_error.powi(2)).sqrt();
+            assert!(
+                (mean - expected).abs() < 5. * error,
+                "{} gives {} +- {} instead of {} +- {}", name, mean, standard_error, expected, expected_error
+            );
+        }
+    }
+
+    // The same point, under a flat ring of light facing it instead of the
+    // sphere. A disk of radius r at height h gives an irradiance of
+    // pi L r^2 / (h^2 + r^2).
+    #[test]
+    fn flat_lights_converge() {
+        // a disk, or a ring when there's a hole
+        let shape = |inner_radius: f32| -> Box<dyn Hitable> {
+            let (center, down) = (Vec3::new(0., HEIGHT, 0.), Vec3::new(0., -1., 0.));
+            let material = Arc::new(DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(RADIANCE)))));
+            if inner_radius == 0. { Box::new(Disk::new(center, down, 1., material)) }
+            else { Box::new(Annulus::new(center, down, inner_radius, 1., material)) }
+        };
+        let disk = |r: f32| r * r / (HEIGHT * HEIGHT + r * r);
+
+        for &(inner_radius, fraction) in [(0., disk(1.)), (0.5, disk(1.) - disk(0.5))].iter() {
+            let mut world: Vec<Box<dyn Hitable>> = vec![
+                Box::new(XzRect::new(-100., 100., -100., 100., 0., Arc::new(
+                    Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(ALBEDO))))
+                ))),
+                shape(inner_radius),
+            ];
+            let lights: Vec<Box<dyn Light>> = vec![Box::new(AreaLight::new(shape(inner_radius)))];
+            let scene = Scene::new(BvhTree::new(&mut world, 0., 1.), lights, Box::new(|_: &Ray| Vec3::zero()));
+
+            let r = Ray { origin: Vec3::new(3., 0.5, 0.), direction: Vec3::new(-3., -0.5, 0.), time: 0. };
+            let expected = (ALBEDO * RADIANCE * fraction) as f64;
+            for name in ["nee", "mis"].iter() {
+                let (mean, standard_error) = estimate(name, &scene, &r);
+                assert!(
+                    (mean - expected).abs() < 5. * standard_error + 1e-4,
+                    "{} gives {} +- {} instead of {}", name, mean, standard_error, expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bsdf_sampling_converges() {
+        assert_converges("path", 1.);
+    }
+
+    #[test]
+    fn light_sampling_converges() {
+        assert_converges("nee", 1.);
+        assert_converges("direct", 1.);
+    }
+
+    #[test]
+    fn mis_converges() {
+        assert_converges("mis", 1.);
+        assert_converges("mis-balance", 1.);
+    }
+
+    #[test]
+    fn surfaces_are_lit_from_behind() {
+        assert_converges("path", -1.);
+        assert_converges("nee", -1.);
+        assert_converges("mis", -1.);
+    }
+}
@@ -0,0 +1,261 @@
+// Lights the integrator can sample directly, instead of waiting for a random
+// bounce to stumble upon them.
+
+use std::f32;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{Hitable, HitRecord};
+use sampling::{self, Onb};
+
+// a direction towards a light, seen from a point of the scene
+#[derive(Debug, Copy, Clone)]
+pub struct LightSample {
+    // unit vector from the point towards the light
+    pub direction: Vec3,
+    // distance to the light along `direction`, for shadow rays
+    pub distance: f32,
+    // light arriving at the point from `direction`, if nothing is in the way
+    pub radiance: Vec3,
+    // density `direction` was drawn with, in solid angle. 1 for lights
+    // reduced to a point or a direction.
+    pub pdf: f32,
+    // the light can't be found by rays bouncing around the scene, so light
+    // sampling is the only way to account for it
+    pub is_delta: bool,
+}
+
+pub trait Light {
+    fn sample(&self, point: Vec3, time: f32) -> Option<LightSample>;
+
+    // density `sample` would pick `direction` with, seen from `point`
+    fn pdf(&self, point: Vec3, direction: Vec3) -> f32;
+
+    // true for the lights seen by the rays escaping the scene
+    fn is_environment(&self) -> bool {
+        false
+    }
+
+    // whether `rec`, found along `ray`, is on the light
+    fn is_hit(&self, _ray: &Ray, _rec: &HitRecord) -> bool {
+        false
+    }
+}
+
+// An emissive shape. It should also be part of the world, so the shape
+// blocks the light and shows up in the render. Only the shapes that can
+// sample their surface can be lights.
+pub struct AreaLight {
+    pub shape: Box<dyn Hitable>,
+}
+
+impl AreaLight {
+    pub fn new(shape: Box<dyn Hitable>) -> Self {
+        assert!(shape.is_samplable(), "An area light needs a shape that can be sampled.");
+        AreaLight {
+            shape,
+        }
+    }
+}
+
+impl Light for AreaLight {
+    fn sample(&self, point: Vec3, time: f32) -> Option<LightSample> {
+        let direction = self.shape.random(point).unit_vector();
+        let pdf = self.shape.pdf_value(point, direction);
+        if pdf <= 0. {
+            return None;
+        }
+
+        let ray = Ray { origin: point, direction, time };
+        let rec = self.shape.hit(&ray, 0.001, f32::MAX)?;
+
+        Some(LightSample {
+            direction,
+            distance: rec.t,
+            radiance: rec.material.emitted(&ray, &rec),
+            pdf,
+            is_delta: false,
+        })
+    }
+
+    fn pdf(&self, point: Vec3, direction: Vec3) -> f32 {
+        self.shape.pdf_value(point, direction)
+    }
+
+    fn is_hit(&self, ray: &Ray, rec: &HitRecord) -> bool {
+        // the shape is found where the world was hit
+        self.shape.hit(ray, 0.001, f32::MAX)
+            .is_some_and(|light_rec| (light_rec.t - rec.t).abs() <= 1e-4 * rec.t.max(1.))
+    }
+}
+
+// emits the same intensity in all directions from a single point,
+// falling off with the square of the distance
+pub struct PointLight {
+    pub position: Vec3,
+    pub intensity: Vec3,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, intensity: Vec3) -> Self {
+        PointLight {
+            position,
+            intensity,
+        }
+    }
+}
+
+impl Light for PointLight {
+    fn sample(&self, point: Vec3, _time: f32) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            radiance: self.intensity / distance.powi(2),
+            pdf: 1.,
+            is_delta: true,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.
+    }
+}
+
+// A point light restricted to a cone around `direction`. The intensity is
+// full up to `falloff_start` degrees off the axis, and fades out smoothly
+// until `total_width` degrees.
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub intensity: Vec3,
+    cos_total_width: f32,
+    cos_falloff_start: f32,
+}
+
+impl SpotLight {
+    pub fn new(position: Vec3, direction: Vec3, intensity: Vec3, total_width: f32, falloff_start: f32) -> Self {
+        SpotLight {
+            position,
+            direction: direction.unit_vector(),
+            intensity,
+            cos_total_width: total_width.to_radians().cos(),
+            cos_falloff_start: falloff_start.min(total_width).to_radians().cos(),
+        }
+    }
+
+    fn falloff(&self, cos_theta: f32) -> f32 {
+        if cos_theta <= self.cos_total_width {
+            return 0.;
+        }
+        if cos_theta >= self.cos_falloff_start {
+            return 1.;
+        }
+        let x = (cos_theta - self.cos_total_width) / (self.cos_falloff_start - self.cos_total_width);
+        x * x * (3. - 2. * x)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, point: Vec3, _time: f32) -> Option<LightSample> {
+        let to_light = self.position - point;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+
+        let falloff = self.falloff(-direction.dot(self.direction));
+        if falloff <= 0. {
+            return None;
+        }
+
+        Some(LightSample {
+            direction,
+            distance,
+            radiance: self.intensity * falloff / distance.powi(2),
+            pdf: 1.,
+            is_delta: true,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.
+    }
+}
+
+// light coming from infinitely far away, all along `direction`
+pub struct DirectionalLight {
+    // direction the light travels in
+    pub direction: Vec3,
+    // power received by a unit area facing the light
+    pub irradiance: Vec3,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, irradiance: Vec3) -> Self {
+        DirectionalLight {
+            direction: direction.unit_vector(),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _point: Vec3, _time: f32) -> Option<LightSample> {
+        Some(LightSample {
+            direction: -self.direction,
+            distance: f32::MAX,
+            radiance: self.irradiance,
+            pdf: 1.,
+            is_delta: true,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.
+    }
+}
+
+// A directional light with the size of a disk in the sky, which gives soft
+// shadows. `angular_diameter` is in degrees, about 0.53 for the real sun.
+// The irradiance is the same as a directional light's, spread over the disk.
+//
+// The disk is much too small to be found by rays escaping the scene, which
+// only see the background, so it's treated as a delta light.
+pub struct SunLight {
+    // direction the light travels in
+    pub direction: Vec3,
+    pub irradiance: Vec3,
+    cos_theta_max: f32,
+}
+
+impl SunLight {
+    pub fn new(direction: Vec3, irradiance: Vec3, angular_diameter: f32) -> Self {
+        SunLight {
+            direction: direction.unit_vector(),
+            irradiance,
+            cos_theta_max: (angular_diameter / 2.).to_radians().cos(),
+        }
+    }
+}
+
+impl Light for SunLight {
+    fn sample(&self, _point: Vec3, _time: f32) -> Option<LightSample> {
+        let pdf = sampling::uniform_cone_pdf(self.cos_theta_max);
+        let direction = Onb::from_w(-self.direction).local(sampling::uniform_cone(self.cos_theta_max));
+
+        // radiance is uniform over the disk, and integrates to the irradiance.
+        // The pdf being the inverse of the disk's solid angle, the estimate
+        // is the irradiance.
+        Some(LightSample {
+            direction,
+            distance: f32::MAX,
+            radiance: self.irradiance * pdf,
+            pdf,
+            is_delta: true,
+        })
+    }
+
+    fn pdf(&self, _point: Vec3, _direction: Vec3) -> f32 {
+        0.
+    }
+}
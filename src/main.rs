@@ -10,6 +10,30 @@ mod camera;
 mod bvh_node;
 mod texture;
 mod noises;
+mod polynomial;
+mod quadrics;
+mod cuboid;
+mod csg;
+mod sdf;
+mod heightfield;
+mod sampling;
+mod rect;
+mod light;
+mod scene;
+mod mis;
+mod integrator;
+mod settings;
+mod distribution;
+mod environment;
+mod sky;
+mod medium;
+mod microfacet;
+mod principled;
+mod bump;
+mod mesh;
+mod subsurface;
+mod diffuse;
+mod scenes;
 
 use rand::Rng;
 
@@ -18,199 +42,37 @@ use std::sync::Arc;
 use std::f32;
 
 use vec3::{Vec3};
-use ray::{Ray};
-use hitable::{Hitable, HitableList, Sphere, MovingSphere};
-use material::{Lambertian, Metal, Dielectric};
 use camera::{Camera};
 use bvh_node::{BvhTree};
-use texture::{ConstantTexture, CheckedTexture, NoiseTexture};
-
-fn background(r: &Ray) -> Vec3 {
-    let unit_direction = r.direction.unit_vector();
-    let t = 0.5 * (unit_direction.y + 1.);
-    Vec3::new(1., 1., 1.) * (1. - t) + Vec3::new(0.5, 0.7, 1.) * t
-}
-
-fn color(r: &Ray, scene: &dyn Hitable, depth: u64) -> Vec3 {
-    let t_min = 0.01;
-    let t_max = f32::MAX;
-    match scene.hit(r, t_min, t_max) {
-        Some(rec) => {
-            match (depth < 50, (*rec.material).scatter(&r, &rec)) {
-                (true, Some(mat_rec)) => {
-                    mat_rec.attenuation * color(&mat_rec.scattered, scene, depth + 1)
-                },
-                _ => Vec3::fromf(0.),
-            }
-        },
-        None => background(r),
-    }
-}
-
-fn create_world() -> HitableList {
-    let beige = Vec3::new(246., 211., 195.) / 255.;
-    let brown = Vec3::new(163., 82., 51.) / 255.;
-
-    let colors = [
-        Vec3::new(139., 75., 98.) / 255.,
-        Vec3::new(187., 111., 107.) / 255.,
-        Vec3::new(234., 150., 116.) / 255.,
-        Vec3::new(252., 188., 128.) / 255.,
-        Vec3::new(247., 226., 156.) / 255.,
-        beige,
-        brown,
-    ];
-
-    let mut world : HitableList;
-
-    let intersects_with_main_spheres = |new_center: Vec3, new_radius: f32| {
-        [
-            (Vec3::new(0., 0., -1.), 0.7),
-            (Vec3::new(-1., 0., -1.), 0.7),
-            (Vec3::new(1., 0., -1.), 0.7),
-        ].iter().fold(false, |acc, (center, radius)| {
-            if acc { return acc };
-            let distance = (new_center - center).length();
-            distance < new_radius || distance < *radius
-        })
-    };
-
-    let random_color_and_position = || {
-        let mut rng = rand::thread_rng();
-        let mut x : f32;
-        let mut z : f32;
-        loop {
-            x = rng.gen_range::<f32>(-5., 5.);
-            z = rng.gen_range::<f32>(-5., 5.);
-            if !intersects_with_main_spheres(Vec3::new(x, -0.3, z), 0.2) { break; }
-        }
-
-        (
-            &colors[rng.gen_range::<u64>(0, colors.len() as u64) as usize],
-            x,
-            z,
-        )
-    };
-
-    world = vec![
-        Box::new(Sphere::new(
-            Vec3::new(0., -100.5 , -1.),
-            100.0,
-            Arc::new(
-                Lambertian::new(
-                    Box::new(
-                        CheckedTexture::new(
-                            Box::new(ConstantTexture::new(Vec3::new(0.8, 0.8, 0.8))),
-                            Box::new(ConstantTexture::new(Vec3::new(0.2, 0.2, 0.2)))
-                        )
-                    )
-                )
-            ),
-        )),
-        Box::new(Sphere::new(
-            Vec3::new(0., 0., -1.),
-            0.5,
-            Arc::new(
-                Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(139., 75., 98.) / 255.)))
-            ),
-        )),
-        Box::new(Sphere::new(
-            Vec3::new(-1., 0., -1.),
-            0.5,
-            Arc::new(
-                Dielectric::new(1.5)
-            ),
-        )),
-        Box::new(Sphere::new(
-            Vec3::new(1., 0., -1.),
-            0.5,
-            Arc::new(
-                Metal::new(beige, 0.)
-            ),
-        )),
-    ];
-
-    for _ in 0..50 {
-        let (color, x, z) = random_color_and_position();
-        let center = Vec3::new(x, -0.3, z);
-        let center_delta_y = rand::thread_rng().gen::<f32>() / 2.;
-        world.push(Box::new(
-            MovingSphere::new(
-                center,
-                center + Vec3::new(0., center_delta_y, 0.),
-                0.,
-                1.,
-                0.2,
-                Arc::new(
-                    Lambertian::new(Box::new(ConstantTexture::new(color.clone())))
-                )
-            )
-        ));
-    }
-
-    for _ in 0..25 {
-        let (color, x, z) = random_color_and_position();
-        let fuzz = rand::thread_rng().gen::<f32>();
-        world.push(Box::new(
-            Sphere::new(
-                Vec3::new(x, -0.3, z),
-                0.2,
-                Arc::new(
-                    Metal::new(color.clone(), fuzz)
-                )
-            )
-        ));
-    }
-
-    for _ in 0..15 {
-        let (_, x, z) = random_color_and_position();
-        world.push(Box::new(
-            Sphere::new(
-                Vec3::new(x, -0.3, z),
-                0.2,
-                Arc::new(
-                    Dielectric::new(1.5)
-                )
-            )
-        ));
-    }
-
-    world
-}
-
-fn two_spheres() -> HitableList {
-    let world: HitableList = vec![
-        Box::new(
-            Sphere::new(
-                Vec3::new(0., -1000., 0.),
-                1000.,
-                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(2.))))
-            )
-        ),
-        Box::new(
-           Sphere::new(
-                Vec3::new(0., 2., 0.),
-                2.,
-                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(2.))))
-            )
-        )
-    ];
-    world
-}
+use integrator::{integrator_from_name};
+use settings::{Settings};
+use environment::{EnvironmentMap};
+use scenes::{Background, scene_from_name, smoke_from_file};
 
 fn main() -> io::Result<()> {
-    let nx = 600;
-    let ny = 400;
-    let ns = 100;
+    let settings = Settings::from_args()?;
+    let integrator = integrator_from_name(&settings.integrator, settings.termination)
+        .expect("settings only accept known integrators");
+    let nx = settings.width;
+    let ny = settings.height;
+    let ns = settings.samples;
 
     println!("P3\n{} {}\n255", nx, ny);
 
-    // let mut world = create_world();
-    let mut world = two_spheres();
+    let (mut world, lights, background) = scene_from_name(&settings.scene)
+        .expect("settings only accept known scenes");
+    let background = match settings.environment {
+        Some(ref path) => Background::Environment(Arc::new(EnvironmentMap::from_hdr(path, 1.))),
+        None => background,
+    };
+    if let Some(ref path) = settings.smoke {
+        world.push(smoke_from_file(path)?);
+    }
 
     let t_min = 0.01;
     let t_max = f32::MAX;
     let tree = BvhTree::new(&mut world, t_min, t_max);
+    let scene = background.scene(tree, lights);
 
     let camera = Camera::new(
         Vec3::new(13., 2., 3.),
@@ -219,8 +81,7 @@ fn main() -> io::Result<()> {
         20.,
         (nx as f32) / (ny as f32),
         0.0,
-        1.,
-        1.5
+        (1., 1.5)
     );
 
     for j in (0..ny).rev() {
@@ -234,12 +95,13 @@ fn main() -> io::Result<()> {
 
                 let r = camera.get_ray(u, v);
 
-                col = col + color(&r, &tree, 0);
+                col = col + integrator.li(&r, &scene);
             }
 
             let rgb = (col / (ns as f32)).sqrt() * 255.99;
 
-            println!("{} {} {}", rgb.r() as u32, rgb.g() as u32, rgb.b() as u32);
+            // lights and highlights go past what the image can hold
+            println!("{} {} {}", rgb.r().min(255.) as u32, rgb.g().min(255.) as u32, rgb.b().min(255.) as u32);
         }
     }
 
@@ -1,25 +1,30 @@
 extern crate rand;
 use self::rand::Rng;
+use std::f32::consts::PI;
+use std::sync::Arc;
 use ray::{Ray};
 use hitable::{HitRecord};
 use vec3::{Vec3};
-use texture::{Texture};
+use texture::{Texture, ConstantTexture};
+use sampling::{self, Onb};
+use microfacet::{fresnel_dielectric};
 
-fn random_point_in_unit_sphere() -> Vec3 {
-    let mut point: Vec3;
-    let mut rng = rand::thread_rng();
-
-    loop {
-        point = Vec3::new(rng.gen(), rng.gen(), rng.gen());
-        if point.length() < 1. { break; }
-    }
-    point
+// the normal of the hit, flipped to the side `ray` comes from, for the
+// materials that look the same from both sides
+pub fn facing_normal(ray: &Ray, rec: &HitRecord) -> Vec3 {
+    if ray.direction.dot(rec.normal) > 0. { -rec.normal } else { rec.normal }
 }
 
 fn reflect(vec: Vec3, normal: Vec3) -> Vec3 {
     vec - 2. * vec.dot(normal) * normal
 }
 
+// probability of a reflection between media of indices n1 and n2
+fn schlick(cosine: f32, n1: f32, n2: f32) -> f32 {
+    let r0 = ((n1 - n2) / (n1 + n2)).powi(2);
+    r0 + (1. - r0) * (1. - cosine).powi(5)
+}
+
 fn refract(vec: Vec3, normal: Vec3, refraction_indices_ratio: f32) -> Option<Vec3> {
     let uv = vec.unit_vector();
     let dt = uv.dot(normal);
@@ -41,8 +46,67 @@ pub struct MaterialRecord {
     pub attenuation: Vec3,
 }
 
-pub trait Material {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<MaterialRecord>;
+// what kind of bounce a sample makes, for the per kind depth limits of
+// the integrators. Glossy reflections count as specular, and scattering
+// inside a medium as volume.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BounceKind {
+    Diffuse,
+    Specular,
+    Transmission,
+    Volume,
+}
+
+// a direction picked by a material for the next bounce
+#[derive(Copy, Clone)]
+pub struct BsdfSample {
+    // unit vector leaving the surface
+    pub direction: Vec3,
+    // bsdf * cosine / pdf, what the light coming from `direction` gets
+    // multiplied by on its way to the viewer
+    pub weight: Vec3,
+    // density the direction was drawn with, in solid angle. Meaningless for
+    // specular samples, which are drawn from a dirac.
+    pub pdf: f32,
+    // the direction comes from a delta lobe (mirror, smooth glass),
+    // which `eval` and `pdf` can't be asked about
+    pub is_specular: bool,
+    pub kind: BounceKind,
+}
+
+// In all the methods below, `ray` is the ray that hit the surface and
+// `direction` a unit vector leaving it, towards where the light comes from.
+pub trait Material: Send + Sync {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample>;
+
+    // bsdf times the cosine between `direction` and the normal.
+    // Zero for delta lobes.
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3;
+
+    // density `sample` would pick `direction` with. Zero for delta lobes.
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32;
+
+    // true when the material only has delta lobes at the hit, in which
+    // case `eval` and `pdf` are zero there
+    fn is_specular(&self, _rec: &HitRecord) -> bool {
+        false
+    }
+
+    // light given off by the surface towards the origin of `ray`
+    fn emitted(&self, _ray: &Ray, _rec: &HitRecord) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<MaterialRecord> {
+        self.sample(ray, rec).map(|sample| MaterialRecord {
+            scattered: Ray {
+                origin: rec.point,
+                direction: sample.direction,
+                time: ray.time,
+            },
+            attenuation: sample.weight,
+        })
+    }
 }
 
 pub struct Lambertian {
@@ -58,70 +122,159 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<MaterialRecord> {
-        Some(MaterialRecord {
-            scattered: Ray {
-                origin: rec.point,
-                direction: rec.normal + random_point_in_unit_sphere(),
-                time: ray.time,
-            },
-            attenuation: self.albedo.value(0., 0., rec.point),
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        // cosine weighted directions cancel out the cosine of the rendering
+        // equation, so the albedo is all that's left of the estimator.
+        let local = sampling::cosine_hemisphere();
+
+        Some(BsdfSample {
+            direction: Onb::from_w(facing_normal(ray, rec)).local(local),
+            weight: self.albedo.value(rec.u, rec.v, rec.point),
+            pdf: sampling::cosine_hemisphere_pdf(local.z),
+            is_specular: false,
+            kind: BounceKind::Diffuse,
         })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let cosine = direction.dot(facing_normal(ray, rec)).max(0.);
+        self.albedo.value(rec.u, rec.v, rec.point) * cosine / PI
+    }
 
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        sampling::cosine_hemisphere_pdf(direction.dot(facing_normal(ray, rec)))
     }
 }
 
-#[derive(Copy, Clone)]
 pub struct Metal {
-    pub albedo: Vec3,
-    pub fuzz: f32,
+    pub albedo: Box<dyn Texture>,
+    pub fuzz: Box<dyn Texture>,
 }
 
 impl Metal {
     pub fn new(albedo: Vec3, fuzz: f32) -> Self {
+        Metal::textured(Box::new(ConstantTexture::new(albedo)), Box::new(ConstantTexture::fromf(fuzz)))
+    }
+
+    pub fn textured(albedo: Box<dyn Texture>, fuzz: Box<dyn Texture>) -> Self {
         Metal {
             albedo,
             fuzz,
         }
     }
+
+    fn fuzz(&self, rec: &HitRecord) -> f32 {
+        self.fuzz.value(rec.u, rec.v, rec.point).x.max(0.)
+    }
+
+    // The fuzzy reflection is the mirror direction plus a point drawn
+    // uniformly in a ball of radius `fuzz`. The density of a direction is
+    // the fraction of the ball's volume along the line it points to.
+    fn fuzz_pdf(fuzz: f32, reflected: Vec3, direction: Vec3) -> f32 {
+        if fuzz == 0. {
+            return 0.;
+        }
+        // distances along `direction` where the line enters and leaves the ball
+        let b = direction.dot(reflected);
+        let discriminant = b * b - (1. - fuzz.powi(2));
+        if discriminant <= 0. {
+            return 0.;
+        }
+        let r1 = (b - discriminant.sqrt()).max(0.);
+        let r2 = (b + discriminant.sqrt()).max(0.);
+        // the integral of r^2 dr along the line, in a ball scaled by `fuzz`
+        (r2.powi(3) - r1.powi(3)) / 3. * sampling::random_in_unit_sphere_pdf() / fuzz.powi(3)
+    }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<MaterialRecord> {
-        let reflected = reflect(ray.direction.unit_vector(), rec.normal);
-
-        let scattered = Ray {
-            origin: rec.point,
-            direction: reflected + self.fuzz * random_point_in_unit_sphere(),
-            time: ray.time,
-        };
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let fuzz = self.fuzz(rec);
+        let normal = facing_normal(ray, rec);
+        let reflected = reflect(ray.direction.unit_vector(), normal);
+        let direction = (reflected + fuzz * sampling::random_in_unit_sphere()).unit_vector();
 
-        if scattered.direction.dot(rec.normal) > 0. {
-            Some(MaterialRecord {
-                scattered,
-                attenuation: self.albedo
+        // directions going under the surface are absorbed
+        if direction.dot(normal) > 0. {
+            Some(BsdfSample {
+                direction,
+                weight: self.albedo.value(rec.u, rec.v, rec.point),
+                pdf: if fuzz == 0. { 1. } else { Metal::fuzz_pdf(fuzz, reflected, direction) },
+                is_specular: fuzz == 0.,
+                kind: BounceKind::Specular,
             })
         } else {
             None
         }
     }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        // weight is the albedo, so bsdf * cosine is albedo * pdf
+        if direction.dot(facing_normal(ray, rec)) > 0. {
+            self.albedo.value(rec.u, rec.v, rec.point) * self.pdf(ray, rec, direction)
+        } else {
+            Vec3::zero()
+        }
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let reflected = reflect(ray.direction.unit_vector(), facing_normal(ray, rec));
+        Metal::fuzz_pdf(self.fuzz(rec), reflected, direction)
+    }
+
+    // a mirror where the fuzz is zero, like the samples say
+    fn is_specular(&self, rec: &HitRecord) -> bool {
+        self.fuzz(rec) == 0.
+    }
 }
 
-#[derive(Copy, Clone)]
+// Glass, water and the like. Light is reflected or refracted at random, with
+// the probability of a reflection given by Schlick's approximation of the
+// Fresnel equations, and is absorbed along the distance it travels inside.
+//
+// Nested media, like a liquid in a glass, are modeled by giving each surface
+// the index of the medium it is in contact with: the glass walls have an
+// outside index of 1 (air), and the surface of the liquid touching the glass
+// has an outside index of 1.5.
 pub struct Dielectric {
-    refraction_index: f32,
+    refraction_index: Box<dyn Texture>,
+    outside_refraction_index: f32,
+    // fraction of each channel absorbed per unit of distance inside
+    absorption: Box<dyn Texture>,
 }
 
 impl Dielectric {
     pub fn new(refraction_index: f32) -> Self {
+        Dielectric::tinted(refraction_index, Vec3::zero())
+    }
+
+    pub fn tinted(refraction_index: f32, absorption: Vec3) -> Self {
+        Dielectric::nested(refraction_index, 1., absorption)
+    }
+
+    pub fn nested(refraction_index: f32, outside_refraction_index: f32, absorption: Vec3) -> Self {
+        Dielectric {
+            outside_refraction_index,
+            ..Dielectric::textured(
+                Box::new(ConstantTexture::fromf(refraction_index)),
+                Box::new(ConstantTexture::new(absorption)),
+            )
+        }
+    }
+
+    // The absorption is looked up where the rays leave the object, which
+    // makes sense for slowly varying tints.
+    pub fn textured(refraction_index: Box<dyn Texture>, absorption: Box<dyn Texture>) -> Self {
         Dielectric {
             refraction_index,
+            outside_refraction_index: 1.,
+            absorption,
         }
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<MaterialRecord> {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
         let is_ray_inside_object = ray.direction.dot(rec.normal) > 0.;
 
         // outward normal is the normal pointing in the opposite
@@ -130,36 +283,334 @@ impl Material for Dielectric {
             if is_ray_inside_object { -rec.normal }
             else { rec.normal };
 
-        let refraction_ratio =
-            if is_ray_inside_object { self.refraction_index }
-            else { 1. / self.refraction_index };
-
-
-        match refract(
-            ray.direction,
-            outward_normal,
-            refraction_ratio
-        ) {
-            Some(refracted) => Some(
-                MaterialRecord {
-                    scattered: Ray {
-                        origin: rec.point,
-                        direction: refracted,
-                        time: ray.time,
-                    },
-                    attenuation: Vec3::fromf(1.),
-                }
-            ),
-            None => Some(
-                MaterialRecord {
-                    scattered: Ray {
-                        origin: rec.point,
-                        direction: reflect(ray.direction, rec.normal),
-                        time: ray.time,
-                    },
-                    attenuation: Vec3::fromf(1.),
-                }
-            ),
+        let refraction_index = self.refraction_index.value(rec.u, rec.v, rec.point).x;
+        let (n1, n2) =
+            if is_ray_inside_object { (refraction_index, self.outside_refraction_index) }
+            else { (self.outside_refraction_index, refraction_index) };
+
+        let refraction_ratio = n1 / n2;
+        let cos_incident = -ray.direction.unit_vector().dot(outward_normal);
+
+        let refracted = refract(ray.direction, outward_normal, refraction_ratio);
+
+        let reflect_probability = match refracted {
+            Some(_) => {
+                // going to a lower index, the approximation has to use
+                // the angle of the transmitted ray
+                let cosine =
+                    if n1 > n2 { (1. - refraction_ratio.powi(2) * (1. - cos_incident.powi(2))).sqrt() }
+                    else { cos_incident };
+                schlick(cosine, n1, n2)
+            },
+            // total internal reflection
+            None => 1.,
+        };
+
+        let (direction, kind) = match refracted {
+            Some(refracted) if rand::thread_rng().gen::<f32>() >= reflect_probability =>
+                (refracted, BounceKind::Transmission),
+            _ => (reflect(ray.direction, rec.normal), BounceKind::Specular),
+        };
+
+        // Beer-Lambert law: the ray has been travelling inside the object
+        // since its origin if it hits the surface from the inside
+        let attenuation =
+            if is_ray_inside_object {
+                let absorption = self.absorption.value(rec.u, rec.v, rec.point);
+                (-absorption * rec.t * ray.direction.length()).exp()
+            } else {
+                Vec3::fromf(1.)
+            };
+
+        Some(
+            BsdfSample {
+                direction: direction.unit_vector(),
+                weight: attenuation,
+                pdf: 1.,
+                is_specular: true,
+                kind,
+            }
+        )
+    }
+
+    fn eval(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn pdf(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> f32 {
+        0.
+    }
+
+    fn is_specular(&self, _rec: &HitRecord) -> bool {
+        true
+    }
+}
+
+// Phase functions, the materials of participating media. Unlike surfaces,
+// they have no normal, and no cosine in `eval`.
+
+// scatters equally in all directions
+pub struct Isotropic {
+    pub albedo: Box<dyn Texture>,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Box<dyn Texture>) -> Self {
+        Isotropic {
+            albedo,
         }
     }
 }
+
+impl Material for Isotropic {
+    fn sample(&self, _ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        Some(BsdfSample {
+            direction: sampling::uniform_sphere(),
+            weight: self.albedo.value(rec.u, rec.v, rec.point),
+            pdf: sampling::uniform_sphere_pdf(),
+            is_specular: false,
+            kind: BounceKind::Volume,
+        })
+    }
+
+    fn eval(&self, _ray: &Ray, rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, rec.point) * sampling::uniform_sphere_pdf()
+    }
+
+    fn pdf(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> f32 {
+        sampling::uniform_sphere_pdf()
+    }
+}
+
+// Henyey-Greenstein's phase function. Light tends to keep going forward for
+// a positive `g`, and to be scattered back for a negative one. It's
+// isotropic for a `g` of 0.
+pub struct HenyeyGreenstein {
+    pub albedo: Box<dyn Texture>,
+    pub g: f32,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Box<dyn Texture>, g: f32) -> Self {
+        HenyeyGreenstein {
+            albedo,
+            // the phase function becomes a dirac at 1 and -1
+            g: g.clamp(-0.99, 0.99),
+        }
+    }
+
+    // density of scattering at an angle of `cos_theta` from the direction
+    // of propagation
+    fn phase(&self, cos_theta: f32) -> f32 {
+        let g2 = self.g * self.g;
+        (1. - g2) / (4. * PI * (1. + g2 - 2. * self.g * cos_theta).powf(1.5))
+    }
+}
+
+impl Material for HenyeyGreenstein {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let mut rng = rand::thread_rng();
+        let (r1, r2) = (rng.gen::<f32>(), rng.gen::<f32>());
+        let g = self.g;
+
+        // inversion of the cdf of the angle
+        let cos_theta =
+            if g.abs() < 1e-3 { 1. - 2. * r1 }
+            else { (1. + g * g - ((1. - g * g) / (1. - g + 2. * g * r1)).powi(2)) / (2. * g) };
+        let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+        let phi = 2. * PI * r2;
+
+        let forward = ray.direction.unit_vector();
+        let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+
+        Some(BsdfSample {
+            direction: Onb::from_w(forward).local(local),
+            weight: self.albedo.value(rec.u, rec.v, rec.point),
+            pdf: self.phase(cos_theta),
+            is_specular: false,
+            kind: BounceKind::Volume,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, rec.point) * self.pdf(ray, rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, _rec: &HitRecord, direction: Vec3) -> f32 {
+        self.phase(ray.direction.unit_vector().dot(direction))
+    }
+}
+
+// Emits light on both of its sides and doesn't reflect any. The color is
+// scaled by `strength`, read from the first channel without clamping, so
+// that a pattern can be made brighter.
+pub struct DiffuseLight {
+    pub emit: Box<dyn Texture>,
+    pub strength: Box<dyn Texture>,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Box<dyn Texture>) -> Self {
+        DiffuseLight::with_strength(emit, Box::new(ConstantTexture::fromf(1.)))
+    }
+
+    pub fn with_strength(emit: Box<dyn Texture>, strength: Box<dyn Texture>) -> Self {
+        DiffuseLight {
+            emit,
+            strength,
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn sample(&self, _ray: &Ray, _rec: &HitRecord) -> Option<BsdfSample> {
+        None
+    }
+
+    fn eval(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> Vec3 {
+        Vec3::zero()
+    }
+
+    fn pdf(&self, _ray: &Ray, _rec: &HitRecord, _direction: Vec3) -> f32 {
+        0.
+    }
+
+    fn emitted(&self, _ray: &Ray, rec: &HitRecord) -> Vec3 {
+        self.emit.value(rec.u, rec.v, rec.point) * self.strength.value(rec.u, rec.v, rec.point).x
+    }
+}
+
+// Blends two materials, like rust patches on a metal: `weight` is the
+// fraction of `b`, read from the first channel of the texture. Each sample
+// is taken from one of them at random, in those proportions.
+pub struct MixMaterial {
+    pub a: Arc<dyn Material>,
+    pub b: Arc<dyn Material>,
+    pub weight: Box<dyn Texture>,
+}
+
+impl MixMaterial {
+    pub fn new(a: Arc<dyn Material>, b: Arc<dyn Material>, weight: Box<dyn Texture>) -> Self {
+        MixMaterial {
+            a,
+            b,
+            weight,
+        }
+    }
+
+    fn weight(&self, rec: &HitRecord) -> f32 {
+        self.weight.scalar(rec.u, rec.v, rec.point)
+    }
+}
+
+impl Material for MixMaterial {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let weight = self.weight(rec);
+        let picked = if rand::thread_rng().gen::<f32>() < weight { &self.b } else { &self.a };
+        let sample = picked.sample(ray, rec)?;
+
+        // the chance of picking a material is its share of the mix, which
+        // cancels out for the delta lobes that the other can't produce
+        if sample.is_specular {
+            return Some(sample);
+        }
+        let pdf = self.pdf(ray, rec, sample.direction);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            weight: self.eval(ray, rec, sample.direction) / pdf,
+            pdf,
+            ..sample
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let weight = self.weight(rec);
+        self.a.eval(ray, rec, direction) * (1. - weight) + self.b.eval(ray, rec, direction) * weight
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let weight = self.weight(rec);
+        self.a.pdf(ray, rec, direction) * (1. - weight) + self.b.pdf(ray, rec, direction) * weight
+    }
+
+    fn is_specular(&self, rec: &HitRecord) -> bool {
+        self.a.is_specular(rec) && self.b.is_specular(rec)
+    }
+
+    fn emitted(&self, ray: &Ray, rec: &HitRecord) -> Vec3 {
+        let weight = self.weight(rec);
+        self.a.emitted(ray, rec) * (1. - weight) + self.b.emitted(ray, rec) * weight
+    }
+}
+
+// A smooth varnish over any material. The coat mirrors the light with the
+// probability given by the Fresnel equations, and lets the rest through to
+// the base, on the way in and again on the way out. The directions aren't
+// bent through the coat, which is considered infinitely thin.
+pub struct CoatedMaterial {
+    pub base: Arc<dyn Material>,
+    pub refraction_index: Box<dyn Texture>,
+}
+
+impl CoatedMaterial {
+    pub fn new(base: Arc<dyn Material>, refraction_index: f32) -> Self {
+        CoatedMaterial::textured(base, Box::new(ConstantTexture::fromf(refraction_index)))
+    }
+
+    pub fn textured(base: Arc<dyn Material>, refraction_index: Box<dyn Texture>) -> Self {
+        CoatedMaterial {
+            base,
+            refraction_index,
+        }
+    }
+
+    // fraction of the light arriving along `direction` reflected by the coat
+    fn reflectance(&self, rec: &HitRecord, direction: Vec3) -> f32 {
+        let refraction_index = self.refraction_index.value(rec.u, rec.v, rec.point).x;
+        fresnel_dielectric(direction.unit_vector().dot(rec.normal).abs(), refraction_index)
+    }
+}
+
+impl Material for CoatedMaterial {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let reflectance = self.reflectance(rec, ray.direction);
+        if rand::thread_rng().gen::<f32>() < reflectance {
+            return Some(BsdfSample {
+                direction: reflect(ray.direction.unit_vector(), rec.normal),
+                weight: Vec3::fromf(1.),
+                pdf: 1.,
+                is_specular: true,
+                kind: BounceKind::Specular,
+            });
+        }
+
+        // what goes through the coat on the way in cancels out with the
+        // chance of getting there, leaving the way out
+        let sample = self.base.sample(ray, rec)?;
+        let transmittance = 1. - self.reflectance(rec, sample.direction);
+        Some(BsdfSample {
+            weight: sample.weight * transmittance,
+            pdf: sample.pdf * (1. - reflectance),
+            ..sample
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let transmittance = (1. - self.reflectance(rec, ray.direction)) * (1. - self.reflectance(rec, direction));
+        self.base.eval(ray, rec, direction) * transmittance
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        self.base.pdf(ray, rec, direction) * (1. - self.reflectance(rec, ray.direction))
+    }
+
+    fn is_specular(&self, rec: &HitRecord) -> bool {
+        self.base.is_specular(rec)
+    }
+
+    fn emitted(&self, ray: &Ray, rec: &HitRecord) -> Vec3 {
+        self.base.emitted(ray, rec) * (1. - self.reflectance(rec, ray.direction))
+    }
+}
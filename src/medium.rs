@@ -0,0 +1,411 @@
+// Participating media: fog, smoke, and the like. A ray going through a
+// medium is scattered at a random distance, exponentially distributed
+// according to the density, and then bounces off in a direction picked by
+// the phase function, which is the material of the medium.
+
+extern crate rand;
+use self::rand::Rng;
+use std::sync::Arc;
+use std::f32;
+use std::fs::File;
+use std::io::{self, Read};
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, Sphere};
+use material::{Material, BsdfSample};
+use noises::{Perlin};
+use sampling::{Onb};
+
+// the scattering event of a ray at distance `t` along it
+pub fn scatter_record<'a>(r: &Ray, t: f32, phase: &'a dyn Material) -> HitRecord<'a> {
+    let frame = Onb::from_w(r.direction);
+    HitRecord {
+        t,
+        point: r.point_at_parameter(t),
+        // phase functions don't have a surface, this is arbitrary
+        normal: -r.direction.unit_vector(),
+        tangent: frame.u,
+        bitangent: frame.v,
+        u: 0.,
+        v: 0.,
+        material: phase,
+    }
+}
+
+// The part of the ray between t_min and t_max that's inside `boundary`,
+// which has to be convex. The entry point is behind the origin of the ray
+// if it starts inside.
+fn boundary_span(boundary: &dyn Hitable, r: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+    let enter = boundary.hit(r, f32::MIN, f32::MAX)?.t;
+    let exit = boundary.hit(r, enter + 0.0001, f32::MAX)?.t;
+
+    let enter = enter.max(t_min);
+    let exit = exit.min(t_max);
+    if enter < exit { Some((enter, exit)) } else { None }
+}
+
+// distance to the next collision in a medium of density `density`
+pub fn free_flight(density: f32) -> f32 {
+    -(1. - rand::thread_rng().gen::<f32>()).ln() / density
+}
+
+// A medium of uniform `density` filling a closed shape. The shape has to be
+// convex: a ray that leaves it is considered out for good.
+pub struct ConstantMedium {
+    pub boundary: Box<dyn Hitable>,
+    pub density: f32,
+    pub phase: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Box<dyn Hitable>, density: f32, phase: Arc<dyn Material>) -> Self {
+        ConstantMedium {
+            boundary,
+            density,
+            phase,
+        }
+    }
+
+    // fog filling the whole scene, which has to fit within `radius` of the origin
+    pub fn global(radius: f32, density: f32, phase: Arc<dyn Material>) -> Self {
+        let boundary = Sphere::new(Vec3::zero(), radius, phase.clone());
+        ConstantMedium::new(Box::new(boundary), density, phase)
+    }
+}
+
+impl Hitable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (enter, exit) = boundary_span(&*self.boundary, r, t_min, t_max)?;
+
+        let ray_length = r.direction.length();
+        let hit_distance = free_flight(self.density);
+        if hit_distance >= (exit - enter) * ray_length {
+            return None;
+        }
+
+        Some(scatter_record(r, enter + hit_distance / ray_length, &*self.phase))
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box(t0, t1)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        match boundary_span(&*self.boundary, r, t_min, t_max) {
+            Some((enter, exit)) => (-self.density * (exit - enter) * r.direction.length()).exp(),
+            None => 1.,
+        }
+    }
+}
+
+// a density varying in space
+pub trait DensityField: Send + Sync {
+    fn density(&self, p: Vec3) -> f32;
+
+    // an upper bound of the density everywhere
+    fn max_density(&self) -> f32;
+}
+
+// so that a field can drive both the density and the emission of a medium
+impl<T: DensityField + ?Sized> DensityField for Arc<T> {
+    fn density(&self, p: Vec3) -> f32 {
+        (**self).density(p)
+    }
+
+    fn max_density(&self) -> f32 {
+        (**self).max_density()
+    }
+}
+
+// Perlin turbulence, for clouds and smoke. The turbulence below `threshold`
+// is cut out, leaving clumps of density separated by empty space.
+pub struct NoiseDensity {
+    pub noise: Perlin,
+    pub scale: f32,
+    pub density: f32,
+    pub threshold: f32,
+}
+
+impl NoiseDensity {
+    pub fn new(scale: f32, density: f32, threshold: f32) -> Self {
+        NoiseDensity {
+            noise: Perlin::new(),
+            scale,
+            density,
+            threshold: threshold.min(0.99),
+        }
+    }
+}
+
+impl DensityField for NoiseDensity {
+    fn density(&self, p: Vec3) -> f32 {
+        let turbulence = self.noise.turb(p * self.scale).min(1.);
+        self.density * ((turbulence - self.threshold) / (1. - self.threshold)).max(0.)
+    }
+
+    fn max_density(&self) -> f32 {
+        self.density
+    }
+}
+
+// Densities sampled on a regular grid filling `bounds`, interpolated
+// linearly between the centers of the voxels and zero outside.
+pub struct VoxelGrid {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+    // x varies fastest, then y, then z
+    pub values: Vec<f32>,
+    pub bounds: Aabb,
+    max_value: f32,
+}
+
+impl VoxelGrid {
+    pub fn new(values: Vec<f32>, nx: usize, ny: usize, nz: usize, bounds: Aabb) -> Self {
+        assert_eq!(Some(values.len()), nx.checked_mul(ny).and_then(|n| n.checked_mul(nz)));
+        let max_value = values.iter().cloned().fold(0., f32::max);
+        VoxelGrid {
+            nx,
+            ny,
+            nz,
+            values,
+            bounds,
+            max_value,
+        }
+    }
+
+    // Reads a grid from a raw binary file: the three dimensions as little
+    // endian u32, followed by the values as little endian f32, and nothing
+    // else.
+    pub fn from_file(path: &str, bounds: Aabb) -> io::Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        let word = |i: usize| -> io::Result<[u8; 4]> {
+            match bytes.get(4 * i..4 * i + 4) {
+                Some(b) => Ok([b[0], b[1], b[2], b[3]]),
+                None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "voxel grid is truncated")),
+            }
+        };
+
+        let nx = u32::from_le_bytes(word(0)?) as usize;
+        let ny = u32::from_le_bytes(word(1)?) as usize;
+        let nz = u32::from_le_bytes(word(2)?) as usize;
+        let count = nx.checked_mul(ny).and_then(|n| n.checked_mul(nz))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "voxel grid is too large"))?;
+        if count.checked_mul(4).and_then(|n| n.checked_add(12)) != Some(bytes.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("voxel grid of {}x{}x{} holds {} bytes of values", nx, ny, nz, bytes.len() - 12),
+            ));
+        }
+        let values = bytes[12..]
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+
+        Ok(VoxelGrid::new(values, nx, ny, nz, bounds))
+    }
+
+    fn value(&self, i: isize, j: isize, k: isize) -> f32 {
+        if i < 0 || j < 0 || k < 0 || i >= self.nx as isize || j >= self.ny as isize || k >= self.nz as isize {
+            return 0.;
+        }
+        self.values[(k as usize * self.ny + j as usize) * self.nx + i as usize]
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, p: Vec3) -> f32 {
+        let size = self.bounds.max - self.bounds.min;
+        // position in voxels, relative to the center of the first one
+        let x = (p.x - self.bounds.min.x) / size.x * self.nx as f32 - 0.5;
+        let y = (p.y - self.bounds.min.y) / size.y * self.ny as f32 - 0.5;
+        let z = (p.z - self.bounds.min.z) / size.z * self.nz as f32 - 0.5;
+        let (i, j, k) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - i, y - j, z - k);
+        let (i, j, k) = (i as isize, j as isize, k as isize);
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let c00 = lerp(self.value(i, j, k), self.value(i + 1, j, k), fx);
+        let c10 = lerp(self.value(i, j + 1, k), self.value(i + 1, j + 1, k), fx);
+        let c01 = lerp(self.value(i, j, k + 1), self.value(i + 1, j, k + 1), fx);
+        let c11 = lerp(self.value(i, j + 1, k + 1), self.value(i + 1, j + 1, k + 1), fx);
+        lerp(lerp(c00, c10, fy), lerp(c01, c11, fy), fz)
+    }
+
+    fn max_density(&self) -> f32 {
+        self.max_value
+    }
+}
+
+// A medium whose density varies, filling a convex shape. Collisions are
+// found with delta tracking: the medium is made uniform with the maximum
+// density, by adding fictitious particles which let the rays go through
+// unchanged. Shadow rays use ratio tracking, which weighs the light by the
+// chance of going through each of those tentative collisions instead of
+// picking one of the two.
+//
+// An emissive medium glows with `emission_color` times the emission field,
+// like the temperature of a fire. It should only be emissive where it has
+// some density, as the light is gathered at the collisions.
+pub struct HeterogeneousMedium {
+    pub boundary: Box<dyn Hitable>,
+    pub density: Box<dyn DensityField>,
+    pub phase: Arc<dyn Material>,
+    pub emission: Option<(Box<dyn DensityField>, Vec3)>,
+    max_density: f32,
+}
+
+impl HeterogeneousMedium {
+    pub fn new(boundary: Box<dyn Hitable>, density: Box<dyn DensityField>, phase: Arc<dyn Material>) -> Self {
+        HeterogeneousMedium {
+            max_density: density.max_density(),
+            boundary,
+            density,
+            phase,
+            emission: None,
+        }
+    }
+
+    pub fn emissive(
+        boundary: Box<dyn Hitable>,
+        density: Box<dyn DensityField>,
+        phase: Arc<dyn Material>,
+        emission: Box<dyn DensityField>,
+        emission_color: Vec3,
+    ) -> Self {
+        HeterogeneousMedium {
+            emission: Some((emission, emission_color)),
+            ..HeterogeneousMedium::new(boundary, density, phase)
+        }
+    }
+}
+
+impl Hitable for HeterogeneousMedium {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        if self.max_density <= 0. {
+            return None;
+        }
+        let (enter, exit) = boundary_span(&*self.boundary, r, t_min, t_max)?;
+
+        let ray_length = r.direction.length();
+        let mut t = enter;
+        loop {
+            t += free_flight(self.max_density) / ray_length;
+            if t >= exit {
+                return None;
+            }
+            // a real collision, or a fictitious one to go through
+            let density = self.density.density(r.point_at_parameter(t));
+            if rand::thread_rng().gen::<f32>() * self.max_density < density {
+                return Some(scatter_record(r, t, self));
+            }
+        }
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box(t0, t1)
+    }
+
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        if self.max_density <= 0. {
+            return 1.;
+        }
+        let (enter, exit) = match boundary_span(&*self.boundary, r, t_min, t_max) {
+            Some(span) => span,
+            None => return 1.,
+        };
+
+        let ray_length = r.direction.length();
+        let mut transmittance = 1.;
+        let mut t = enter;
+        loop {
+            t += free_flight(self.max_density) / ray_length;
+            if t >= exit {
+                return transmittance;
+            }
+            transmittance *= 1. - self.density.density(r.point_at_parameter(t)) / self.max_density;
+        }
+    }
+}
+
+// The collisions found by `hit` have the medium as their material, which
+// scatters with the phase function and adds the emission.
+impl Material for HeterogeneousMedium {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.phase.sample(ray, rec)
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.phase.eval(ray, rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        self.phase.pdf(ray, rec, direction)
+    }
+
+    fn emitted(&self, _ray: &Ray, rec: &HitRecord) -> Vec3 {
+        match self.emission {
+            Some((ref emission, color)) => {
+                // collisions happen proportionally to the density, which
+                // the emission gathered there makes up for
+                let density = self.density.density(rec.point);
+                if density > 0. { color * emission.density(rec.point) / density } else { Vec3::zero() }
+            },
+            None => Vec3::zero(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn grid_file(name: &str, dimensions: [u32; 3], values: &[f32]) -> String {
+        let mut bytes = vec![];
+        for d in dimensions.iter() {
+            bytes.extend_from_slice(&d.to_le_bytes());
+        }
+        for v in values {
+            bytes.extend_from_slice(&v.to_le_bytes());
+        }
+        let path = env::temp_dir().join(name);
+        fs::write(&path, bytes).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn bounds() -> Aabb {
+        Aabb { min: Vec3::zero(), max: Vec3::fromf(1.) }
+    }
+
+    #[test]
+    fn voxel_grid_reads_its_values() {
+        let path = grid_file("voxel_grid_reads_its_values", [2, 1, 1], &[0.5, 2.]);
+        let grid = VoxelGrid::from_file(&path, bounds()).unwrap();
+        assert_eq!(grid.values, vec![0.5, 2.]);
+        assert_eq!(grid.max_density(), 2.);
+    }
+
+    #[test]
+    fn voxel_grid_rejects_a_wrong_value_count() {
+        let path = grid_file("voxel_grid_rejects_a_wrong_value_count", [2, 2, 2], &[1., 2., 3.]);
+        let error = VoxelGrid::from_file(&path, bounds()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let path = env::temp_dir().join("voxel_grid_rejects_a_truncated_header");
+        fs::write(&path, [2, 0, 0, 0, 2]).unwrap();
+        let error = VoxelGrid::from_file(path.to_str().unwrap(), bounds()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn voxel_grid_rejects_an_overflowing_size() {
+        let path = grid_file("voxel_grid_rejects_an_overflowing_size", [u32::MAX, u32::MAX, u32::MAX], &[1.]);
+        let error = VoxelGrid::from_file(&path, bounds()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,297 @@
+// Triangle meshes, for surfaces with no simple equation and for displacement
+// mapping: the surface is cut into small triangles whose vertices are moved
+// along the normal by a height texture, changing the silhouette and the
+// shadows where a bump map only changes the shading.
+//
+// The triangles are kept in a tree of their own, so a mesh is a single
+// hitable for `BvhTree` whatever its number of triangles.
+
+use std::sync::Arc;
+use std::f32::consts::PI;
+use std::collections::HashMap;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, tangent_towards, bitangent_towards, intersect_triangle};
+use material::{Material};
+use texture::{Texture};
+
+// most triangles in a leaf of the tree
+const LEAF_SIZE: usize = 4;
+// default padding of the boxes, so flat triangles lying in an axis plane
+// still have boxes with some thickness for the slab test
+const DEFAULT_PADDING: f32 = 1e-4;
+
+// a node of the tree, a leaf when it has triangles
+struct Node {
+    aabb: Aabb,
+    // the children, or the triangles from start to start + count
+    left: usize,
+    right: usize,
+    start: usize,
+    count: usize,
+}
+
+// Triangles sharing vertices, wound counterclockwise seen from outside.
+// The normals are smooth: the vertex normals are averaged from the faces
+// around them, vertices at the same position being welded together.
+pub struct Mesh {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f32, f32)>,
+    triangles: Vec<[usize; 3]>,
+    pub material: Arc<dyn Material>,
+    // added around every box of the tree
+    padding: f32,
+    nodes: Vec<Node>,
+}
+
+// the vertices at the same position as each vertex, by the first of them
+fn welded(positions: &[Vec3]) -> Vec<usize> {
+    let mut first = HashMap::new();
+    positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| *first.entry((p.x.to_bits(), p.y.to_bits(), p.z.to_bits())).or_insert(i))
+        .collect()
+}
+
+fn smooth_normals(positions: &[Vec3], triangles: &[[usize; 3]]) -> Vec<Vec3> {
+    let welds = welded(positions);
+    let mut sums = vec![Vec3::zero(); positions.len()];
+    for t in triangles {
+        // the cross product is weighted by the area of the face
+        let face = (positions[t[1]] - positions[t[0]]).cross(positions[t[2]] - positions[t[0]]);
+        for &i in t {
+            sums[welds[i]] = sums[welds[i]] + face;
+        }
+    }
+    welds
+        .iter()
+        .map(|&w| if sums[w].squared_length() > 0. { sums[w].unit_vector() } else { Vec3::zero() })
+        .collect()
+}
+
+fn triangle_box(positions: &[Vec3], t: &[usize; 3]) -> Aabb {
+    let (a, b, c) = (positions[t[0]], positions[t[1]], positions[t[2]]);
+    Aabb {
+        min: Vec3::new(a.x.min(b.x).min(c.x), a.y.min(b.y).min(c.y), a.z.min(b.z).min(c.z)),
+        max: Vec3::new(a.x.max(b.x).max(c.x), a.y.max(b.y).max(c.y), a.z.max(b.z).max(c.z)),
+    }
+}
+
+impl Mesh {
+    pub fn new(positions: Vec<Vec3>, uvs: Vec<(f32, f32)>, triangles: Vec<[usize; 3]>, material: Arc<dyn Material>) -> Self {
+        assert_eq!(positions.len(), uvs.len(), "a mesh needs texture coordinates for every vertex");
+        let normals = smooth_normals(&positions, &triangles);
+        let mut mesh = Mesh {
+            positions,
+            normals,
+            uvs,
+            triangles,
+            material,
+            padding: DEFAULT_PADDING,
+            nodes: vec![],
+        };
+        mesh.build();
+        mesh
+    }
+
+    // A sphere cut into `rate` slices around its axis and half as many
+    // from pole to pole, with the texture coordinates of `Sphere`.
+    pub fn sphere(center: Vec3, radius: f32, rate: usize, material: Arc<dyn Material>) -> Self {
+        let slices = rate.max(3);
+        let stacks = (rate / 2).max(2);
+        let mut positions = vec![];
+        let mut uvs = vec![];
+        for j in 0..=stacks {
+            for i in 0..=slices {
+                let (u, v) = (i as f32 / slices as f32, j as f32 / stacks as f32);
+                // the last slice closes on the first one, at the same positions
+                let phi = (1. - (i % slices) as f32 / slices as f32) * 2. * PI - PI;
+                let theta = v * PI - PI / 2.;
+                let point =
+                    if j == 0 { Vec3::new(0., -1., 0.) }
+                    else if j == stacks { Vec3::new(0., 1., 0.) }
+                    else { Vec3::new(theta.cos() * phi.cos(), theta.sin(), theta.cos() * phi.sin()) };
+                positions.push(center + point * radius);
+                uvs.push((u, v));
+            }
+        }
+
+        let index = |i: usize, j: usize| j * (slices + 1) + i;
+        let mut triangles = vec![];
+        for j in 0..stacks {
+            for i in 0..slices {
+                let (a, b, c, d) = (index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1));
+                // the triangles touching the poles have two vertices there
+                if j > 0 {
+                    triangles.push([a, b, c]);
+                }
+                if j < stacks - 1 {
+                    triangles.push([a, c, d]);
+                }
+            }
+        }
+        Mesh::new(positions, uvs, triangles, material)
+    }
+
+    // padding of the boxes of the tree, for triangles lying in an axis plane
+    pub fn with_padding(mut self, padding: f32) -> Self {
+        self.padding = padding;
+        self.build();
+        self
+    }
+
+    // The mesh with every triangle cut in four, `levels` times. The new
+    // vertices are in the middle of the edges, shared by the triangles on
+    // both sides so that displacing them opens no cracks.
+    pub fn subdivided(&self, levels: u32) -> Mesh {
+        let mut positions = self.positions.clone();
+        let mut uvs = self.uvs.clone();
+        let mut triangles = self.triangles.clone();
+        for _ in 0..levels {
+            let mut middles = HashMap::new();
+            let mut middle = |a: usize, b: usize| {
+                *middles.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    positions.push((positions[a] + positions[b]) * 0.5);
+                    uvs.push(((uvs[a].0 + uvs[b].0) * 0.5, (uvs[a].1 + uvs[b].1) * 0.5));
+                    positions.len() - 1
+                })
+            };
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (middle(a, b), middle(b, c), middle(c, a));
+                    vec![[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]
+                })
+                .collect();
+        }
+        Mesh::new(positions, uvs, triangles, self.material.clone()).with_padding(self.padding)
+    }
+
+    // The mesh with its vertices moved along their normal by `scale` times
+    // the first channel of `height`. The displacement is only as detailed as
+    // the mesh, which should be subdivided first. Vertices at the same
+    // position move together, even on texture seams.
+    pub fn displaced(&self, height: &dyn Texture, scale: f32) -> Mesh {
+        let welds = welded(&self.positions);
+        let offsets: Vec<f32> = (0..self.positions.len())
+            .map(|i| {
+                let (u, v) = self.uvs[i];
+                height.scalar(u, v, self.positions[i]) * scale
+            })
+            .collect();
+        let positions = welds
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| self.positions[i] + self.normals[w] * offsets[w])
+            .collect();
+        Mesh::new(positions, self.uvs.clone(), self.triangles.clone(), self.material.clone())
+            .with_padding(self.padding)
+    }
+
+    fn padded(&self, aabb: Aabb) -> Aabb {
+        Aabb {
+            min: aabb.min - self.padding,
+            max: aabb.max + self.padding,
+        }
+    }
+
+    // Builds the tree, splitting the triangles in two halves along the
+    // longest axis of their centers, and reorders them for the leaves.
+    fn build(&mut self) {
+        let boxes: Vec<Aabb> = self.triangles.iter().map(|t| triangle_box(&self.positions, t)).collect();
+        let mut order: Vec<usize> = (0..self.triangles.len()).collect();
+        self.nodes = vec![];
+        if !order.is_empty() {
+            self.build_node(&boxes, &mut order, 0);
+        }
+        self.triangles = order.iter().map(|&i| self.triangles[i]).collect();
+    }
+
+    fn build_node(&mut self, boxes: &[Aabb], order: &mut [usize], start: usize) -> usize {
+        let aabb = order[1..]
+            .iter()
+            .fold(boxes[order[0]], |acc, &i| acc.surrounding_box(&boxes[i]));
+        let node = self.nodes.len();
+        self.nodes.push(Node { aabb: self.padded(aabb), left: 0, right: 0, start, count: order.len() });
+        if order.len() <= LEAF_SIZE {
+            return node;
+        }
+
+        let center = |i: usize| (boxes[i].min + boxes[i].max) * 0.5;
+        let extent = aabb.max - aabb.min;
+        let axis = if extent.x > extent.y && extent.x > extent.z { 0 } else if extent.y > extent.z { 1 } else { 2 };
+        order.sort_by(|&a, &b| center(a)[axis].total_cmp(&center(b)[axis]));
+
+        let half = order.len() / 2;
+        let (left_order, right_order) = order.split_at_mut(half);
+        let left = self.build_node(boxes, left_order, start);
+        let right = self.build_node(boxes, right_order, start + half);
+        self.nodes[node].left = left;
+        self.nodes[node].right = right;
+        self.nodes[node].count = 0;
+        node
+    }
+
+    fn record(&self, t: &[usize; 3], r: &Ray, distance: f32, b1: f32, b2: f32) -> HitRecord<'_> {
+        let b0 = 1. - b1 - b2;
+        let (p0, p1, p2) = (self.positions[t[0]], self.positions[t[1]], self.positions[t[2]]);
+        let (uv0, uv1, uv2) = (self.uvs[t[0]], self.uvs[t[1]], self.uvs[t[2]]);
+
+        let geometric = (p1 - p0).cross(p2 - p0).unit_vector();
+        let smooth = self.normals[t[0]] * b0 + self.normals[t[1]] * b1 + self.normals[t[2]] * b2;
+        let normal = if smooth.squared_length() > 0. { smooth.unit_vector() } else { geometric };
+
+        // the tangent follows u across the face, and the bitangent v
+        let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+        let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+        let det = du1 * dv2 - du2 * dv1;
+        let dp_du = if det != 0. { ((p1 - p0) * dv2 - (p2 - p0) * dv1) / det } else { p1 - p0 };
+        let dp_dv = if det != 0. { ((p2 - p0) * du1 - (p1 - p0) * du2) / det } else { p2 - p0 };
+        let tangent = tangent_towards(normal, dp_du);
+
+        HitRecord {
+            t: distance,
+            point: r.point_at_parameter(distance),
+            normal,
+            tangent,
+            bitangent: bitangent_towards(normal, tangent, dp_dv),
+            u: uv0.0 * b0 + uv1.0 * b1 + uv2.0 * b2,
+            v: uv0.1 * b0 + uv1.1 * b1 + uv2.1 * b2,
+            material: &*self.material,
+        }
+    }
+}
+
+impl Hitable for Mesh {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let mut closest = t_max;
+        let mut hit = None;
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.aabb.hit(r, t_min, closest) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.push(node.left);
+                stack.push(node.right);
+                continue;
+            }
+            for triangle in &self.triangles[node.start..node.start + node.count] {
+                let (p0, p1, p2) = (self.positions[triangle[0]], self.positions[triangle[1]], self.positions[triangle[2]]);
+                if let Some((distance, b1, b2)) = intersect_triangle(r, p0, p1, p2, t_min, closest) {
+                    closest = distance;
+                    hit = Some((triangle, b1, b2));
+                }
+            }
+        }
+        hit.map(|(triangle, b1, b2)| self.record(triangle, r, closest, b1, b2))
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.aabb)
+    }
+}
@@ -0,0 +1,531 @@
+// Microfacet materials: the surface is made of tiny mirror facets, their
+// orientations following the GGX (Trowbridge-Reitz) distribution. The
+// rougher the surface, the more spread out the facets. All the functions
+// below work in the local frame of the surface, the normal being +z, with
+// `wo` pointing towards the viewer and `wi` towards the light.
+
+extern crate rand;
+use self::rand::Rng;
+use std::f32::consts::PI;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{HitRecord};
+use material::{Material, BsdfSample, BounceKind, facing_normal};
+use sampling::{Onb};
+use texture::{Texture, ConstantTexture};
+
+// The facets can be spread differently along x, the tangent of the
+// surface, and y, for brushed metals.
+#[derive(Debug, Copy, Clone)]
+pub struct Ggx {
+    pub alpha_x: f32,
+    pub alpha_y: f32,
+}
+
+impl Ggx {
+    // Roughness is the square root of alpha, which makes it perceptually
+    // linear. Perfectly smooth surfaces need the specular materials.
+    pub fn from_roughness(roughness: f32) -> Self {
+        Ggx::anisotropic(roughness, 0.)
+    }
+
+    // The surface brushed along the tangent, for an `anisotropy` from 0 to
+    // 1: smoother along it and rougher across it, which stretches the
+    // highlights across the tangent (after Disney's principled brdf).
+    pub fn anisotropic(roughness: f32, anisotropy: f32) -> Self {
+        let alpha = roughness.powi(2);
+        let aspect = (1. - 0.9 * anisotropy.clamp(0., 1.)).sqrt();
+        Ggx::from_alphas(alpha * aspect, alpha / aspect)
+    }
+
+    pub fn from_alphas(alpha_x: f32, alpha_y: f32) -> Self {
+        Ggx {
+            alpha_x: alpha_x.max(0.001),
+            alpha_y: alpha_y.max(0.001),
+        }
+    }
+
+    // density of facets oriented along `m`, per unit of projected area
+    pub fn d(&self, m: Vec3) -> f32 {
+        if m.z <= 0. {
+            return 0.;
+        }
+        let t = (m.x / self.alpha_x).powi(2) + (m.y / self.alpha_y).powi(2) + m.z * m.z;
+        1. / (PI * self.alpha_x * self.alpha_y * t * t)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0. {
+            return 0.;
+        }
+        // squared tangent of the angle with the normal, times the squared
+        // alpha along the projection of `w` on the surface
+        let alpha2_tan2 = ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / cos2;
+        ((1. + alpha2_tan2).sqrt() - 1.) / 2.
+    }
+
+    // fraction of the facets facing `w` that are visible from it
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1. / (1. + self.lambda(w))
+    }
+
+    // Smith's height correlated shadowing-masking
+    pub fn g2(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Samples the normal of a facet visible from `wo`, which has to be
+    // above the surface ("Sampling the GGX Distribution of Visible
+    // Normals", Heitz 2018).
+    pub fn sample_visible_normal(&self, wo: Vec3) -> Vec3 {
+        let mut rng = rand::thread_rng();
+        let (u1, u2) = (rng.gen::<f32>(), rng.gen::<f32>());
+
+        // stretches the view to the configuration of a unit roughness
+        let vh = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit_vector();
+        let length2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 =
+            if length2 > 0. { Vec3::new(-vh.y, vh.x, 0.) / length2.sqrt() }
+            else { Vec3::new(1., 0., 0.) };
+        let t2 = vh.cross(t1);
+
+        // point on the projected hemisphere, half of the disk being squashed
+        let r = u1.sqrt();
+        let phi = 2. * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1. + vh.z);
+        let p2 = (1. - s) * (1. - p1 * p1).sqrt() + s * r * phi.sin();
+        let nh = p1 * t1 + p2 * t2 + (1. - p1 * p1 - p2 * p2).max(0.).sqrt() * vh;
+
+        Vec3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(0.)).unit_vector()
+    }
+
+    // density of `sample_visible_normal` returning `m`
+    pub fn visible_normal_pdf(&self, wo: Vec3, m: Vec3) -> f32 {
+        if wo.z <= 0. {
+            return 0.;
+        }
+        self.g1(wo) * wo.dot(m).max(0.) * self.d(m) / wo.z
+    }
+}
+
+// Reflectance of a metal with a complex index of refraction eta + i k, per
+// channel, for light arriving at an angle of `cos_theta` with the normal.
+pub fn fresnel_conductor(cos_theta: f32, eta: Vec3, k: Vec3) -> Vec3 {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = cos_theta.clamp(0., 1.).powi(2);
+        let sin2 = 1. - cos2;
+        let eta2 = eta * eta;
+        let k2 = k * k;
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4. * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+        let t2 = 2. * cos_theta * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+    Vec3::new(channel(eta.x, k.x), channel(eta.y, k.y), channel(eta.z, k.z))
+}
+
+// Reflectance of an interface between two dielectrics, `eta` being the
+// index on the other side over the index on the side of the light. A
+// negative `cos_theta` means the light comes from the other side.
+pub fn fresnel_dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let (cos_i, eta) =
+        if cos_theta < 0. { (-cos_theta, 1. / eta) }
+        else { (cos_theta, eta) };
+    let cos_i = cos_i.min(1.);
+
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    // total internal reflection
+    if sin2_t >= 1. {
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) / 2.
+}
+
+// mirror of `w` around `m`
+pub fn reflect(w: Vec3, m: Vec3) -> Vec3 {
+    2. * w.dot(m) * m - w
+}
+
+// `w` refracted through a facet of normal `m` on its side, `eta` being the
+// index on the other side over the index on the side of `w`
+fn refract(w: Vec3, m: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = w.dot(m);
+    let sin2_t = (1. - cos_i * cos_i).max(0.) / (eta * eta);
+    if sin2_t >= 1. {
+        return None;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * m)
+}
+
+// Rough metal. `eta` and `k` are the complex index of refraction, for red,
+// green and blue, which can vary over the surface like a tarnish. An anisotropic one is brushed along the tangents of the
+// surface, or along `tangents` when set.
+pub struct RoughConductor {
+    pub eta: Box<dyn Texture>,
+    pub k: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+    pub anisotropy: Box<dyn Texture>,
+    // alpha along the tangent and along the bitangent, in place of the
+    // roughness and the anisotropy when set
+    pub alphas: Option<(Box<dyn Texture>, Box<dyn Texture>)>,
+    pub tangents: Option<Box<dyn Texture>>,
+}
+
+impl RoughConductor {
+    pub fn new(eta: Vec3, k: Vec3, roughness: f32) -> Self {
+        RoughConductor::textured(
+            Box::new(ConstantTexture::new(eta)),
+            Box::new(ConstantTexture::new(k)),
+            Box::new(ConstantTexture::fromf(roughness)),
+        )
+    }
+
+    pub fn textured(eta: Box<dyn Texture>, k: Box<dyn Texture>, roughness: Box<dyn Texture>) -> Self {
+        RoughConductor {
+            eta,
+            k,
+            roughness,
+            anisotropy: Box::new(ConstantTexture::fromf(0.)),
+            alphas: None,
+            tangents: None,
+        }
+    }
+
+    // from 0, isotropic, to 1
+    pub fn with_anisotropy(self, anisotropy: f32) -> Self {
+        self.with_anisotropy_texture(Box::new(ConstantTexture::fromf(anisotropy)))
+    }
+
+    pub fn with_anisotropy_texture(mut self, anisotropy: Box<dyn Texture>) -> Self {
+        self.anisotropy = anisotropy;
+        self
+    }
+
+    // the spread of the facets along the tangent and the bitangent, set directly
+    pub fn with_alphas(mut self, alpha_x: Box<dyn Texture>, alpha_y: Box<dyn Texture>) -> Self {
+        self.alphas = Some((alpha_x, alpha_y));
+        self
+    }
+
+    pub fn with_tangents(mut self, tangents: Box<dyn Texture>) -> Self {
+        self.tangents = Some(tangents);
+        self
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> Ggx {
+        match &self.alphas {
+            Some((alpha_x, alpha_y)) => Ggx::from_alphas(
+                alpha_x.scalar(rec.u, rec.v, rec.point),
+                alpha_y.scalar(rec.u, rec.v, rec.point),
+            ),
+            None => Ggx::anisotropic(
+                self.roughness.scalar(rec.u, rec.v, rec.point),
+                self.anisotropy.scalar(rec.u, rec.v, rec.point),
+            ),
+        }
+    }
+
+    fn frame(&self, ray: &Ray, rec: &HitRecord) -> (Onb, Vec3) {
+        tangent_frame(ray, rec, self.tangents.as_deref())
+    }
+
+    fn fresnel(&self, rec: &HitRecord, cos_theta: f32) -> Vec3 {
+        fresnel_conductor(
+            cos_theta,
+            self.eta.value(rec.u, rec.v, rec.point),
+            self.k.value(rec.u, rec.v, rec.point),
+        )
+    }
+
+    pub fn gold(roughness: f32) -> Self {
+        RoughConductor::new(Vec3::new(0.143, 0.374, 1.442), Vec3::new(3.983, 2.385, 1.603), roughness)
+    }
+
+    pub fn copper(roughness: f32) -> Self {
+        RoughConductor::new(Vec3::new(0.200, 0.924, 1.102), Vec3::new(3.912, 2.452, 2.142), roughness)
+    }
+
+    pub fn aluminium(roughness: f32) -> Self {
+        RoughConductor::new(Vec3::new(1.657, 0.880, 0.521), Vec3::new(9.224, 6.270, 4.837), roughness)
+    }
+
+    pub fn silver(roughness: f32) -> Self {
+        RoughConductor::new(Vec3::new(0.155, 0.117, 0.138), Vec3::new(4.828, 3.122, 2.147), roughness)
+    }
+}
+
+// local frame of a hit, with the normal on the side of the viewer for
+// materials that look the same from both sides
+pub fn two_sided_frame(ray: &Ray, rec: &HitRecord) -> (Onb, Vec3) {
+    let wo = -ray.direction.unit_vector();
+    let frame = Onb::from_w(facing_normal(ray, rec));
+    let wo = frame.to_local(wo);
+    (frame, wo)
+}
+
+// Same, with x along the tangent of the hit for anisotropic materials.
+// `tangents` turns it, like a normal map: red and green are the coordinates
+// along the tangent and the bitangent, from -1 to 1 mapped to 0 to 1.
+pub fn tangent_frame(ray: &Ray, rec: &HitRecord, tangents: Option<&dyn Texture>) -> (Onb, Vec3) {
+    let wo = -ray.direction.unit_vector();
+    let normal = facing_normal(ray, rec);
+    let tangent = match tangents {
+        Some(tangents) => {
+            let local = tangents.value(rec.u, rec.v, rec.point) * 2. - Vec3::fromf(1.);
+            rec.tangent * local.x + rec.bitangent * local.y
+        }
+        None => rec.tangent,
+    };
+    let frame = Onb::from_wu(normal, tangent);
+    let wo = frame.to_local(wo);
+    (frame, wo)
+}
+
+impl Material for RoughConductor {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let (frame, wo) = self.frame(ray, rec);
+        let distribution = self.distribution(rec);
+        if wo.z <= 0. {
+            return None;
+        }
+
+        let m = distribution.sample_visible_normal(wo);
+        let wi = reflect(wo, m);
+        if wi.z <= 0. {
+            return None;
+        }
+
+        // D and the cosines cancel out with the density of the visible normals
+        let fresnel = self.fresnel(rec, wo.dot(m));
+        Some(BsdfSample {
+            direction: frame.local(wi),
+            weight: fresnel * distribution.g2(wo, wi) / distribution.g1(wo),
+            pdf: distribution.visible_normal_pdf(wo, m) / (4. * wo.dot(m)),
+            is_specular: false,
+            kind: BounceKind::Specular,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let (frame, wo) = self.frame(ray, rec);
+        let distribution = self.distribution(rec);
+        let wi = frame.to_local(direction);
+        if wo.z <= 0. || wi.z <= 0. {
+            return Vec3::zero();
+        }
+
+        let m = (wo + wi).unit_vector();
+        let fresnel = self.fresnel(rec, wo.dot(m));
+        // the cosine of wi cancels out with the one of the brdf
+        fresnel * distribution.d(m) * distribution.g2(wo, wi) / (4. * wo.z)
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let (frame, wo) = self.frame(ray, rec);
+        let distribution = self.distribution(rec);
+        let wi = frame.to_local(direction);
+        if wo.z <= 0. || wi.z <= 0. {
+            return 0.;
+        }
+
+        let m = (wo + wi).unit_vector();
+        distribution.visible_normal_pdf(wo, m) / (4. * wo.dot(m))
+    }
+}
+
+// Rough glass, reflecting and refracting light through the facets, with
+// the probabilities given by the Fresnel equations ("Microfacet Models for
+// Refraction through Rough Surfaces", Walter et al. 2007).
+pub struct RoughDielectric {
+    pub refraction_index: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f32, roughness: f32) -> Self {
+        RoughDielectric::textured(
+            Box::new(ConstantTexture::fromf(refraction_index)),
+            Box::new(ConstantTexture::fromf(roughness)),
+        )
+    }
+
+    pub fn textured(refraction_index: Box<dyn Texture>, roughness: Box<dyn Texture>) -> Self {
+        RoughDielectric {
+            refraction_index,
+            roughness,
+        }
+    }
+
+    fn lobe(&self, rec: &HitRecord) -> DielectricLobe {
+        DielectricLobe::new(
+            self.refraction_index.value(rec.u, rec.v, rec.point).x,
+            self.roughness.scalar(rec.u, rec.v, rec.point),
+        )
+    }
+}
+
+impl Material for RoughDielectric {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        self.lobe(rec).sample(ray, rec)
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.lobe(rec).eval(ray, rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        self.lobe(rec).pdf(ray, rec, direction)
+    }
+}
+
+// rough glass with its parameters at a hit
+#[derive(Copy, Clone)]
+pub struct DielectricLobe {
+    pub refraction_index: f32,
+    pub distribution: Ggx,
+}
+
+// The local frame of a hit on a dielectric, flipped so the viewer is above
+// the surface, and the index of the other side over the index of the
+// viewer's side.
+struct DielectricFrame {
+    frame: Onb,
+    wo: Vec3,
+    eta: f32,
+}
+
+impl DielectricLobe {
+    pub fn new(refraction_index: f32, roughness: f32) -> Self {
+        DielectricLobe {
+            refraction_index,
+            distribution: Ggx::from_roughness(roughness),
+        }
+    }
+
+    fn frame(&self, ray: &Ray, rec: &HitRecord) -> DielectricFrame {
+        let wo = -ray.direction.unit_vector();
+        let entering = wo.dot(rec.normal) > 0.;
+        let (normal, eta) =
+            if entering { (rec.normal, self.refraction_index) }
+            else { (-rec.normal, 1. / self.refraction_index) };
+        let frame = Onb::from_w(normal);
+        DielectricFrame {
+            wo: frame.to_local(wo),
+            frame,
+            eta,
+        }
+    }
+
+    // The normal of the facet scattering `wo` into `wi`, facing up, and the
+    // density of picking `wi` knowing it. None when the facet would face
+    // away from one of them.
+    fn facet(&self, wo: Vec3, wi: Vec3, eta: f32) -> Option<(Vec3, f32)> {
+        let reflected = wi.z > 0.;
+        let half = if reflected { wo + wi } else { wo + wi * eta };
+        if half.squared_length() == 0. {
+            return None;
+        }
+        let m = half.unit_vector();
+        let m = if m.z < 0. { -m } else { m };
+        if wo.dot(m) <= 0. || wi.dot(m) * wi.z <= 0. {
+            return None;
+        }
+
+        let jacobian =
+            if reflected { 1. / (4. * wo.dot(m)) }
+            else { wi.dot(m).abs() / (wi.dot(m) + wo.dot(m) / eta).powi(2) };
+        Some((m, jacobian))
+    }
+
+    pub fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let DielectricFrame { frame, wo, eta } = self.frame(ray, rec);
+        if wo.z <= 0. {
+            return None;
+        }
+
+        let m = self.distribution.sample_visible_normal(wo);
+        let reflectance = fresnel_dielectric(wo.dot(m), eta);
+
+        let (wi, kind) =
+            if rand::thread_rng().gen::<f32>() < reflectance {
+                (reflect(wo, m), BounceKind::Specular)
+            } else {
+                (refract(wo, m, eta)?, BounceKind::Transmission)
+            };
+        if (kind == BounceKind::Specular) != (wi.z > 0.) {
+            return None;
+        }
+
+        let (_, jacobian) = self.facet(wo, wi, eta)?;
+        let probability = if kind == BounceKind::Specular { reflectance } else { 1. - reflectance };
+        // radiance gets compressed into smaller solid angles
+        // going into denser media
+        let scale = if kind == BounceKind::Specular { 1. } else { 1. / (eta * eta) };
+
+        Some(BsdfSample {
+            direction: frame.local(wi),
+            weight: Vec3::fromf(scale * self.distribution.g2(wo, wi) / self.distribution.g1(wo)),
+            pdf: self.distribution.visible_normal_pdf(wo, m) * jacobian * probability,
+            is_specular: false,
+            kind,
+        })
+    }
+
+    pub fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let DielectricFrame { frame, wo, eta } = self.frame(ray, rec);
+        let wi = frame.to_local(direction);
+        if wo.z <= 0. || wi.z == 0. {
+            return Vec3::zero();
+        }
+        let (m, _) = match self.facet(wo, wi, eta) {
+            Some(facet) => facet,
+            None => return Vec3::zero(),
+        };
+
+        let reflectance = fresnel_dielectric(wo.dot(m), eta);
+        let d = self.distribution.d(m);
+        let g = self.distribution.g2(wo, wi);
+
+        // the cosine of wi cancels out with the one of the bsdf
+        let value =
+            if wi.z > 0. {
+                reflectance * d * g / (4. * wo.z)
+            } else {
+                let denominator = (wi.dot(m) + wo.dot(m) / eta).powi(2);
+                (1. - reflectance) * d * g * wi.dot(m).abs() * wo.dot(m) / (wo.z * denominator) / (eta * eta)
+            };
+        Vec3::fromf(value)
+    }
+
+    pub fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let DielectricFrame { frame, wo, eta } = self.frame(ray, rec);
+        let wi = frame.to_local(direction);
+        if wo.z <= 0. || wi.z == 0. {
+            return 0.;
+        }
+        let (m, jacobian) = match self.facet(wo, wi, eta) {
+            Some(facet) => facet,
+            None => return 0.,
+        };
+
+        let reflectance = fresnel_dielectric(wo.dot(m), eta);
+        let probability = if wi.z > 0. { reflectance } else { 1. - reflectance };
+        self.distribution.visible_normal_pdf(wo, m) * jacobian * probability
+    }
+}
@@ -0,0 +1,23 @@
+// Multiple importance sampling. When the same light can be reached with two
+// sampling strategies, each strategy's estimate is weighted so the weights
+// sum to one, favoring the strategy that's most likely to produce the sample.
+// Both strategies then take care of the cases they are good at: the bsdf for
+// glossy surfaces and big lights, light sampling for small lights.
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Heuristic {
+    Balance,
+    Power,
+}
+
+impl Heuristic {
+    // weight of a sample drawn with density `pdf`, which the other
+    // strategy would have drawn with density `other_pdf`
+    pub fn weight(&self, pdf: f32, other_pdf: f32) -> f32 {
+        let (a, b) = match self {
+            Heuristic::Balance => (pdf, other_pdf),
+            Heuristic::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+        if a + b > 0. { a / (a + b) } else { 0. }
+    }
+}
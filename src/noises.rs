@@ -35,9 +35,9 @@ fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32 {
   let vv = ease_in_out(v);
   let ww = ease_in_out(w);
   let mut acc = 0.0;
-  for i_usize in 0..2 {
-    for j_usize in 0..2 {
-      for k_usize in 0..2 {
+  for (i_usize, plane) in c.iter().enumerate() {
+    for (j_usize, row) in plane.iter().enumerate() {
+      for (k_usize, corner) in row.iter().enumerate() {
         let i = i_usize as f32;
         let j = j_usize as f32;
         let k = k_usize as f32;
@@ -46,7 +46,7 @@ fn perlin_interp(c: [[[Vec3; 2]; 2]; 2], u: f32, v: f32, w: f32) -> f32 {
           (i * uu + (1. - i) * (1. - uu)) *
           (j * vv + (1. - j) * (1. - vv)) *
           (k * ww + (1. - k) * (1. - ww)) *
-          c[i_usize][j_usize][k_usize].dot(weight_v);
+          corner.dot(weight_v);
       }
     }
   }
@@ -89,9 +89,9 @@ impl Perlin {
       ]
     ];
 
-    for di in 0..2 {
-      for dj in 0..2 {
-        for dk in 0..2 {
+    for (di, plane) in c.iter_mut().enumerate() {
+      for (dj, row) in plane.iter_mut().enumerate() {
+        for (dk, corner) in row.iter_mut().enumerate() {
           // we have to convert to i32 because unsigned numbers (usize)
           // don't support negative values, and p.x.floor() can be negative
           // which results in a panic.
@@ -100,12 +100,12 @@ impl Perlin {
             self.perm_y[((j + (dj as i32)) & 255) as usize] ^
             self.perm_z[((k + (dk as i32)) & 255) as usize]
           ) as usize;
-          c[di][dj][dk] = self.ran_vec[float_index];
+          *corner = self.ran_vec[float_index];
         }
       }
     }
 
-    return perlin_interp(c, u, v, w);
+    perlin_interp(c, u, v, w)
   }
 
   pub fn turb(&self, p: Vec3) -> f32 {
@@ -0,0 +1,187 @@
+// Real root finders for the low degree polynomials we get when intersecting
+// rays with analytic surfaces. Everything is computed in f64 because the
+// quartic of a torus loses most of its precision in f32.
+
+use std::f64::consts::PI;
+
+// roots of a*x^2 + b*x + c, sorted in ascending order.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return vec![];
+        }
+        return vec![-c / b];
+    }
+
+    let discriminant = b * b - 4. * a * c;
+
+    if discriminant < 0. {
+        return vec![];
+    }
+
+    // avoids the catastrophic cancellation of the textbook formula
+    // when b^2 is much bigger than 4ac
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    let (x1, x2) =
+        if q == 0. { (0., 0.) }
+        else { (q / a, c / q) };
+
+    if x1 < x2 { vec![x1, x2] } else { vec![x2, x1] }
+}
+
+// real roots of x^3 + a*x^2 + b*x + c, sorted in ascending order.
+pub fn solve_normalized_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let q = (a * a - 3. * b) / 9.;
+    let r = (2. * a * a * a - 9. * a * b + 27. * c) / 54.;
+    let q3 = q * q * q;
+    // how far the roots are from being repeated
+    let discriminant = r * r - q3;
+    let tolerance = 1e-12 * q3.abs().max(r * r);
+
+    let mut roots =
+        if discriminant < -tolerance {
+            // three real roots
+            let theta = (r / q3.sqrt()).clamp(-1., 1.).acos();
+            let m = -2. * q.sqrt();
+            vec![
+                m * (theta / 3.).cos() - a / 3.,
+                m * ((theta + 2. * PI) / 3.).cos() - a / 3.,
+                m * ((theta - 2. * PI) / 3.).cos() - a / 3.,
+            ]
+        } else if discriminant <= tolerance {
+            // a double root, or a triple one when r is 0
+            let s = r.cbrt();
+            vec![-2. * s - a / 3., s - a / 3., s - a / 3.]
+        } else {
+            let big_a = -r.signum() * (r.abs() + discriminant.sqrt()).cbrt();
+            let big_b = if big_a == 0. { 0. } else { q / big_a };
+            vec![big_a + big_b - a / 3.]
+        };
+
+    // degenerate coefficients can leave NaNs behind
+    roots.retain(|x| x.is_finite());
+    roots.sort_by(|x, y| x.total_cmp(y));
+    roots
+}
+
+fn eval_quartic(coefs: [f64; 5], x: f64) -> f64 {
+    (((coefs[0] * x + coefs[1]) * x + coefs[2]) * x + coefs[3]) * x + coefs[4]
+}
+
+fn eval_quartic_derivative(coefs: [f64; 5], x: f64) -> f64 {
+    ((4. * coefs[0] * x + 3. * coefs[1]) * x + 2. * coefs[2]) * x + coefs[3]
+}
+
+// real roots of a*x^4 + b*x^3 + c*x^2 + d*x + e, sorted in ascending order.
+// Uses Ferrari's method on the depressed quartic, then polishes each root
+// with a couple of Newton iterations on the original polynomial.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return solve_cubic(b, c, d, e);
+    }
+
+    let (b, c, d, e) = (b / a, c / a, d / a, e / a);
+
+    // x = y - b/4 gives y^4 + p*y^2 + q*y + r = 0
+    let shift = b / 4.;
+    let p = c - 6. * shift * shift;
+    let q = d - 2. * c * shift + 8. * shift * shift * shift;
+    let r = e - d * shift + c * shift * shift - 3. * shift * shift * shift * shift;
+
+    let mut roots: Vec<f64> =
+        if q.abs() < 1e-12 {
+            // biquadratic: solve for y^2
+            solve_quadratic(1., p, r)
+                .into_iter()
+                .filter(|z| *z >= 0.)
+                .flat_map(|z| vec![-z.sqrt(), z.sqrt()])
+                .collect()
+        } else {
+            // the resolvent cubic always has a positive root when q != 0
+            let m = solve_normalized_cubic(p, p * p / 4. - r, -q * q / 8.)
+                .into_iter()
+                .fold(0., f64::max);
+
+            if m <= 0. {
+                vec![]
+            } else {
+                let s = (2. * m).sqrt();
+                let mut ys = solve_quadratic(1., -s, p / 2. + m + q / (2. * s));
+                ys.extend(solve_quadratic(1., s, p / 2. + m - q / (2. * s)));
+                ys
+            }
+        };
+
+    let coefs = [1., b, c, d, e];
+    for root in roots.iter_mut() {
+        let mut x = *root - shift;
+        for _ in 0..2 {
+            let derivative = eval_quartic_derivative(coefs, x);
+            if derivative.abs() > 1e-12 {
+                x -= eval_quartic(coefs, x) / derivative;
+            }
+        }
+        *root = x;
+    }
+
+    // degenerate coefficients can leave NaNs behind
+    roots.retain(|x| x.is_finite());
+    roots.sort_by(|x, y| x.total_cmp(y));
+    roots
+}
+
+// real roots of a*x^3 + b*x^2 + c*x + d, sorted in ascending order.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return solve_quadratic(b, c, d);
+    }
+    solve_normalized_cubic(b / a, c / a, d / a)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "got {:?} instead of {:?}", roots, expected);
+        for (root, expected_root) in roots.iter().zip(expected) {
+            assert!((root - expected_root).abs() < 1e-6, "got {:?} instead of {:?}", roots, expected);
+        }
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(1., -6., 11., -6.), &[1., 2., 3.]);
+        // (x - 1)(x^2 + 1)
+        assert_roots(solve_cubic(2., -2., 2., -2.), &[1.]);
+        // (x - 1)^2 (x + 2)
+        assert_roots(solve_cubic(1., 0., -3., 2.), &[-2., 1., 1.]);
+        // (x + 1)^2 (x - 2)
+        assert_roots(solve_cubic(1., 0., -3., -2.), &[-1., -1., 2.]);
+        // (x - 1)^3
+        assert_roots(solve_cubic(1., -3., 3., -1.), &[1., 1., 1.]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(solve_quartic(1., -10., 35., -50., 24.), &[1., 2., 3., 4.]);
+        // (x - 1)(x + 2)(x^2 + 1)
+        assert_roots(solve_quartic(1., 1., -1., 1., -2.), &[-2., 1.]);
+        // (x^2 + 1)(x^2 + 4)
+        assert_roots(solve_quartic(1., 0., 5., 0., 4.), &[]);
+        // (x - 1)^2 (x - 3)^2
+        assert_roots(solve_quartic(1., -8., 22., -24., 9.), &[1., 1., 3., 3.]);
+        // (x - 1)^2 (x + 1)(x - 2)
+        assert_roots(solve_quartic(1., -3., 1., 3., -2.), &[-1., 1., 1., 2.]);
+        // (x - 2)(x - 1)(x + 1)(x + 3), scaled
+        assert_roots(solve_quartic(0.5, 0.5, -3.5, -0.5, 3.), &[-3., -1., 1., 2.]);
+    }
+
+    #[test]
+    fn nan_coefficients_give_no_roots() {
+        assert_roots(solve_cubic(1., f64::NAN, 0., 1.), &[]);
+        assert_roots(solve_quartic(1., 0., f64::NAN, 0., -1.), &[]);
+    }
+}
@@ -0,0 +1,429 @@
+// A principled material, after Disney's: a handful of intuitive parameters
+// covering most opaque and transparent surfaces, instead of combining
+// lambertian, metal and glass by hand. Every parameter is a texture, the
+// scalar ones being read from the first channel.
+//
+// The surface is made of:
+// - a clear coat on top, a smooth varnish reflecting some of the light,
+// - a metal, in proportion to `metallic`, reflecting the base color,
+// - a rough glass, in proportion to `transmission`, tinted by the base color,
+// - a plastic otherwise: a diffuse base under a dielectric specular layer.
+//
+// A sample picks one of the lobes, with about the chance of it reflecting
+// the light, and is weighted against the density of all of them.
+
+extern crate rand;
+use self::rand::Rng;
+use std::f32::consts::PI;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{HitRecord};
+use texture::{Texture, ConstantTexture};
+use material::{Material, BsdfSample, BounceKind};
+use microfacet::{self, Ggx, DielectricLobe};
+use sampling::{self};
+
+const CLEARCOAT_ROUGHNESS: f32 = 0.1;
+// reflectance at normal incidence of the clear coat, an index of 1.5
+const CLEARCOAT_F0: f32 = 0.04;
+
+pub struct Principled {
+    pub base_color: Box<dyn Texture>,
+    pub metallic: Box<dyn Texture>,
+    pub roughness: Box<dyn Texture>,
+    // brushing of the specular lobe along the tangents, or `tangents`
+    pub anisotropic: Box<dyn Texture>,
+    pub tangents: Option<Box<dyn Texture>>,
+    // reflectance of the plastic at normal incidence, 0.5 being 4%
+    pub specular: Box<dyn Texture>,
+    pub clearcoat: Box<dyn Texture>,
+    // grazing retro-reflection of cloth, whitening the diffuse base
+    pub sheen: Box<dyn Texture>,
+    pub transmission: Box<dyn Texture>,
+    pub emission: Box<dyn Texture>,
+    pub refraction_index: Box<dyn Texture>,
+}
+
+fn constant(value: f32) -> Box<dyn Texture> {
+    Box::new(ConstantTexture::fromf(value))
+}
+
+fn schlick(f0: Vec3, f90: f32, cos_theta: f32) -> Vec3 {
+    f0 + (Vec3::fromf(f90) - f0) * (1. - cos_theta.clamp(0., 1.)).powi(5)
+}
+
+impl Principled {
+    // a rough white plastic tinted by `base_color`, the other parameters
+    // being set with the struct update syntax
+    pub fn new(base_color: Box<dyn Texture>) -> Self {
+        Principled {
+            base_color,
+            metallic: constant(0.),
+            roughness: constant(0.5),
+            anisotropic: constant(0.),
+            tangents: None,
+            specular: constant(0.5),
+            clearcoat: constant(0.),
+            sheen: constant(0.),
+            transmission: constant(0.),
+            emission: constant(0.),
+            refraction_index: constant(1.5),
+        }
+    }
+
+    pub fn metal(base_color: Box<dyn Texture>, roughness: Box<dyn Texture>) -> Self {
+        Principled {
+            metallic: constant(1.),
+            roughness,
+            ..Principled::new(base_color)
+        }
+    }
+
+    pub fn glass(tint: Box<dyn Texture>, roughness: Box<dyn Texture>, refraction_index: f32) -> Self {
+        Principled {
+            roughness,
+            transmission: constant(1.),
+            refraction_index: constant(refraction_index),
+            ..Principled::new(tint)
+        }
+    }
+
+    // the parameters at a hit
+    fn lobes(&self, ray: &Ray, rec: &HitRecord) -> Lobes {
+        let (frame, wo) = microfacet::tangent_frame(ray, rec, self.tangents.as_deref());
+        let metallic = self.metallic.scalar(rec.u, rec.v, rec.point);
+        let transmission = self.transmission.scalar(rec.u, rec.v, rec.point);
+        let roughness = self.roughness.scalar(rec.u, rec.v, rec.point);
+        let base_color = self.base_color.value(rec.u, rec.v, rec.point);
+
+        let metal = metallic;
+        let glass = (1. - metallic) * transmission;
+        let plastic = (1. - metallic) * (1. - transmission);
+
+        let clearcoat = self.clearcoat.scalar(rec.u, rec.v, rec.point);
+        let specular = self.specular.scalar(rec.u, rec.v, rec.point);
+        let coat = clearcoat * schlick(Vec3::fromf(CLEARCOAT_F0), 1., wo.z).x;
+        // the metal and the plastic share a single specular lobe, Schlick's
+        // approximation being linear in the reflectance
+        let specular_f0 = metal * base_color + Vec3::fromf(plastic * 0.08 * specular);
+        let specular_f90 = metal + plastic;
+        // the light not reflected by the plastic's coating reaches the base
+        let diffuse = plastic * (1. - schlick(Vec3::fromf(0.08 * specular), 1., wo.z).x);
+
+        // chance of picking each lobe, from how much light they reflect
+        let specular_weight = schlick(specular_f0, specular_f90, wo.z).luminance();
+        let diffuse_weight = diffuse * base_color.luminance().max(0.1);
+        let total = specular_weight + diffuse_weight + glass;
+        let (specular_chance, diffuse_chance, glass_chance) =
+            if total > 0. { (specular_weight / total, diffuse_weight / total, glass / total) }
+            else { (0., 0., 0.) };
+
+        Lobes {
+            frame,
+            wo,
+            base_color,
+            distribution: Ggx::anisotropic(roughness, self.anisotropic.scalar(rec.u, rec.v, rec.point)),
+            coat_distribution: Ggx::from_roughness(CLEARCOAT_ROUGHNESS),
+            clearcoat,
+            coat_chance: coat,
+            specular_f0,
+            specular_f90,
+            diffuse,
+            sheen: self.sheen.scalar(rec.u, rec.v, rec.point),
+            glass,
+            dielectric: DielectricLobe::new(self.refraction_index.value(rec.u, rec.v, rec.point).x, roughness),
+            specular_chance,
+            diffuse_chance,
+            glass_chance,
+        }
+    }
+}
+
+// Everything needed to evaluate the material at a hit. The opaque lobes
+// look the same from both sides, in `frame` which has the viewer above.
+struct Lobes {
+    frame: sampling::Onb,
+    wo: Vec3,
+    base_color: Vec3,
+    distribution: Ggx,
+    coat_distribution: Ggx,
+    clearcoat: f32,
+    // chance of sampling the clear coat, also the light it reflects away
+    // from the layers below
+    coat_chance: f32,
+    specular_f0: Vec3,
+    specular_f90: f32,
+    // weight of the diffuse base
+    diffuse: f32,
+    sheen: f32,
+    // weight of the glass
+    glass: f32,
+    dielectric: DielectricLobe,
+    // chances of the lobes under the clear coat, summing to 1
+    specular_chance: f32,
+    diffuse_chance: f32,
+    glass_chance: f32,
+}
+
+impl Lobes {
+    // bsdf times cosine of a microfacet reflection lobe
+    fn reflection(distribution: &Ggx, fresnel: Vec3, wo: Vec3, wi: Vec3) -> Vec3 {
+        let m = (wo + wi).unit_vector();
+        fresnel * distribution.d(m) * distribution.g2(wo, wi) / (4. * wo.z)
+    }
+
+    fn reflection_pdf(distribution: &Ggx, wo: Vec3, wi: Vec3) -> f32 {
+        let m = (wo + wi).unit_vector();
+        distribution.visible_normal_pdf(wo, m) / (4. * wo.dot(m))
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        let wo = self.wo;
+        let wi = self.frame.to_local(direction);
+        let mut value = Vec3::zero();
+
+        if wo.z > 0. && wi.z > 0. {
+            let m = (wo + wi).unit_vector();
+            let cos_d = wi.dot(m);
+
+            let specular_fresnel = schlick(self.specular_f0, self.specular_f90, cos_d);
+            value = value + Lobes::reflection(&self.distribution, specular_fresnel, wo, wi);
+
+            // the sheen takes over the base color at grazing angles
+            let sheen = self.sheen * (1. - cos_d).powi(5);
+            let diffuse_color = self.base_color * (1. - sheen) + Vec3::fromf(sheen);
+            value = value + diffuse_color * self.diffuse * wi.z / PI;
+        }
+
+        if self.glass > 0. {
+            let glass = self.dielectric.eval(ray, rec, direction);
+            // only the light going through is tinted
+            let transmitted = direction.dot(rec.normal) * ray.direction.dot(rec.normal) > 0.;
+            let tint = if transmitted { self.base_color } else { Vec3::fromf(1.) };
+            value = value + glass * tint * self.glass;
+        }
+
+        value *= 1. - self.coat_chance;
+        if self.clearcoat > 0. && wo.z > 0. && wi.z > 0. {
+            let m = (wo + wi).unit_vector();
+            let fresnel = schlick(Vec3::fromf(CLEARCOAT_F0), 1., wo.dot(m)) * self.clearcoat;
+            value = value + Lobes::reflection(&self.coat_distribution, fresnel, wo, wi);
+        }
+        value
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        let wo = self.wo;
+        let wi = self.frame.to_local(direction);
+        let mut pdf = 0.;
+
+        if wo.z > 0. && wi.z > 0. {
+            pdf += self.specular_chance * Lobes::reflection_pdf(&self.distribution, wo, wi);
+            pdf += self.diffuse_chance * sampling::cosine_hemisphere_pdf(wi.z);
+        }
+        if self.glass_chance > 0. {
+            pdf += self.glass_chance * self.dielectric.pdf(ray, rec, direction);
+        }
+
+        pdf *= 1. - self.coat_chance;
+        if self.coat_chance > 0. && wo.z > 0. && wi.z > 0. {
+            pdf += self.coat_chance * Lobes::reflection_pdf(&self.coat_distribution, wo, wi);
+        }
+        pdf
+    }
+}
+
+// The reflection of `wo` on the facet `m`. None when it goes under the
+// surface, where the glass could be mistaken for having scattered it.
+fn reflect_above(wo: Vec3, m: Vec3) -> Option<Vec3> {
+    let wi = microfacet::reflect(wo, m);
+    if wi.z > 0. { Some(wi) } else { None }
+}
+
+impl Material for Principled {
+    fn sample(&self, ray: &Ray, rec: &HitRecord) -> Option<BsdfSample> {
+        let lobes = self.lobes(ray, rec);
+        let wo = lobes.wo;
+        let mut rng = rand::thread_rng();
+
+        let (direction, kind) =
+            if rng.gen::<f32>() < lobes.coat_chance {
+                if wo.z <= 0. {
+                    return None;
+                }
+                let m = lobes.coat_distribution.sample_visible_normal(wo);
+                (lobes.frame.local(reflect_above(wo, m)?), BounceKind::Specular)
+            } else {
+                let choice = rng.gen::<f32>();
+                if choice < lobes.glass_chance {
+                    let sample = lobes.dielectric.sample(ray, rec)?;
+                    (sample.direction, sample.kind)
+                } else if wo.z <= 0. {
+                    return None;
+                } else if choice < lobes.glass_chance + lobes.specular_chance {
+                    let m = lobes.distribution.sample_visible_normal(wo);
+                    (lobes.frame.local(reflect_above(wo, m)?), BounceKind::Specular)
+                } else {
+                    (lobes.frame.local(sampling::cosine_hemisphere()), BounceKind::Diffuse)
+                }
+            };
+
+        let pdf = lobes.pdf(ray, rec, direction);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(BsdfSample {
+            direction,
+            weight: lobes.eval(ray, rec, direction) / pdf,
+            pdf,
+            is_specular: false,
+            kind,
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> Vec3 {
+        self.lobes(ray, rec).eval(ray, rec, direction)
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: Vec3) -> f32 {
+        self.lobes(ray, rec).pdf(ray, rec, direction)
+    }
+
+    fn emitted(&self, _ray: &Ray, rec: &HitRecord) -> Vec3 {
+        self.emission.value(rec.u, rec.v, rec.point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 20000;
+    // brighter in red, so that the red channel bounds the others
+    const BASE_COLOR: (f32, f32, f32) = (0.9, 0.5, 0.2);
+    const REFRACTION_INDEX: f32 = 1.5;
+
+    // mean and standard error of each channel
+    fn estimate<I: Iterator<Item = Vec3>>(values: I) -> (Vec3, Vec3) {
+        let values: Vec<Vec3> = values.collect();
+        let n = values.len() as f32;
+        let mean = values.iter().fold(Vec3::zero(), |sum, x| sum + *x) / n;
+        let variance = values.iter().fold(Vec3::zero(), |sum, x| sum + (*x - mean) * (*x - mean)) / (n - 1.);
+        (mean, Vec3::new((variance.x / n).sqrt(), (variance.y / n).sqrt(), (variance.z / n).sqrt()))
+    }
+
+    // a hit on the xz plane, seen from above or from below
+    fn hit(material: &Principled, cos_theta: f32, inside: bool) -> (Ray, HitRecord<'_>) {
+        let rec = HitRecord {
+            t: 1.,
+            point: Vec3::zero(),
+            normal: Vec3::new(0., 1., 0.),
+            tangent: Vec3::new(1., 0., 0.),
+            bitangent: Vec3::new(0., 0., 1.),
+            u: 0.5,
+            v: 0.5,
+            material,
+        };
+        let sin_theta = (1. - cos_theta * cos_theta).sqrt();
+        let side = if inside { -1. } else { 1. };
+        let ray = Ray {
+            origin: Vec3::new(sin_theta, side * cos_theta, 0.),
+            direction: Vec3::new(-sin_theta, -side * cos_theta, 0.),
+            time: 0.,
+        };
+        (ray, rec)
+    }
+
+    // The fraction of the light coming from the viewer's direction that the
+    // surface scatters, as a furnace would show it, estimated with the
+    // material's own sampling. Also returns the energy, which doesn't count
+    // the change of radiance of the light going through the surface.
+    fn sampled_albedo(material: &Principled, cos_theta: f32, inside: bool) -> ((Vec3, Vec3), (Vec3, Vec3)) {
+        let (ray, rec) = hit(material, cos_theta, inside);
+        let samples: Vec<Option<BsdfSample>> = (0..SAMPLES).map(|_| material.sample(&ray, &rec)).collect();
+
+        for sample in samples.iter().flatten() {
+            // whichever lobe it comes from, a sample has the density and
+            // weight the material gives its direction
+            let pdf = material.pdf(&ray, &rec, sample.direction);
+            let weight = material.eval(&ray, &rec, sample.direction) / pdf;
+            assert!((sample.pdf - pdf).abs() <= 1e-3 * pdf, "sampled with pdf {} instead of {}", sample.pdf, pdf);
+            assert!(
+                (sample.weight - weight).length() <= 1e-3 * weight.length(),
+                "sampled with weight {:?} instead of {:?}", sample.weight, weight,
+            );
+        }
+
+        // index on the other side over the index on the viewer's
+        let eta = if inside { 1. / REFRACTION_INDEX } else { REFRACTION_INDEX };
+        let transmitted = |direction: Vec3| direction.dot(rec.normal) * ray.direction.dot(rec.normal) > 0.;
+        let albedo = estimate(samples.iter().map(|sample| sample.map_or(Vec3::zero(), |sample| sample.weight)));
+        let energy = estimate(samples.iter().map(|sample| match sample {
+            Some(sample) if transmitted(sample.direction) => sample.weight * eta * eta,
+            Some(sample) => sample.weight,
+            None => Vec3::zero(),
+        }));
+        (albedo, energy)
+    }
+
+    // the same albedo, evaluating the material in uniform directions
+    fn evaluated_albedo(material: &Principled, cos_theta: f32, inside: bool) -> (Vec3, Vec3) {
+        let (ray, rec) = hit(material, cos_theta, inside);
+        estimate((0..SAMPLES).map(|_| {
+            let direction = sampling::uniform_sphere();
+            material.eval(&ray, &rec, direction) / sampling::uniform_sphere_pdf()
+        }))
+    }
+
+    #[test]
+    fn furnace() {
+        let (r, g, b) = BASE_COLOR;
+        let base_color = Vec3::new(r, g, b);
+        for &(metallic, transmission) in [(0., 0.), (0., 0.5), (0., 1.), (0.5, 0.), (0.5, 1.), (1., 0.)].iter() {
+            for &roughness in [0.05, 0.3, 0.7, 1.].iter() {
+                for &(clearcoat, sheen) in [(0., 0.), (0., 1.), (1., 0.)].iter() {
+                    let material = Principled {
+                        metallic: constant(metallic),
+                        transmission: constant(transmission),
+                        roughness: constant(roughness),
+                        clearcoat: constant(clearcoat),
+                        sheen: constant(sheen),
+                        refraction_index: constant(REFRACTION_INDEX),
+                        ..Principled::new(Box::new(ConstantTexture::new(base_color)))
+                    };
+                    for &inside in [false, true].iter() {
+                        for &cos_theta in [1., 0.6, 0.05].iter() {
+                            let case = format!(
+                                "metallic {}, transmission {}, roughness {}, clearcoat {}, sheen {}, inside {}, cos {}",
+                                metallic, transmission, roughness, clearcoat, sheen, inside, cos_theta,
+                            );
+                            let ((albedo, error), (energy, energy_error)) = sampled_albedo(&material, cos_theta, inside);
+
+                            // no energy is created, the red channel being
+                            // the brightest
+                            assert!(energy.x - 5. * energy_error.x <= 1.001, "{}: energy {:?} +- {:?}", case, energy, energy_error);
+                            // Smooth metals reflect at least their color.
+                            // Rough ones lose the light bouncing more than
+                            // once between the facets.
+                            if metallic == 1. && clearcoat == 0. && roughness <= 0.3 {
+                                assert!(albedo.x > 0.9 * r && albedo.z > 0.9 * b, "{}: albedo {:?} +- {:?}", case, albedo, error);
+                            }
+                            // Uniform directions rarely find the peaks of the
+                            // smooth lobes, so only the rough ones are also
+                            // compared to the evaluated albedo.
+                            if roughness >= 0.7 && clearcoat == 0. {
+                                let (evaluated, evaluated_error) = evaluated_albedo(&material, cos_theta, inside);
+                                let tolerance = (error * error + evaluated_error * evaluated_error).x.sqrt() * 5. + 0.005;
+                                assert!(
+                                    (albedo.x - evaluated.x).abs() < tolerance,
+                                    "{}: sampled albedo {:?} +- {:?}, evaluated {:?} +- {:?}",
+                                    case, albedo, error, evaluated, evaluated_error,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,633 @@
+// Analytic primitives other than spheres. Cylinders, cones, tori and
+// paraboloids are defined around the y axis, with `center` being the center
+// of their base, which keeps the intersection math in local coordinates.
+
+extern crate rand;
+use self::rand::Rng;
+use std::sync::Arc;
+use std::f32;
+use std::f32::consts::PI;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, tangent_towards, bitangent_towards};
+use material::{Material};
+use polynomial::{solve_quadratic, solve_quartic};
+use sampling::{Onb};
+
+// angle around the y axis, mapped to [0, 1]
+fn azimuth_u(p: Vec3) -> f32 {
+    (p.z.atan2(p.x) + PI) / (2. * PI)
+}
+
+// The tangent of the shapes around the y axis: their u goes around it, and
+// along x on their caps.
+fn around_y(normal: Vec3) -> Vec3 {
+    let around = normal.cross(Vec3::new(0., 1., 0.));
+    tangent_towards(normal, if around.squared_length() > 1e-8 { around } else { Vec3::new(1., 0., 0.) })
+}
+
+// and their v goes up their sides, and along z on their caps
+fn up_y(normal: Vec3, tangent: Vec3) -> Vec3 {
+    let around = normal.cross(Vec3::new(0., 1., 0.));
+    bitangent_towards(normal, tangent, if around.squared_length() > 1e-8 { Vec3::new(0., 1., 0.) } else { Vec3::new(0., 0., 1.) })
+}
+
+// uv of a point on a cap of radius `radius` centered on the y axis
+fn cap_uv(p: Vec3, radius: f32) -> (f32, f32) {
+    ((p.x / radius + 1.) / 2., (p.z / radius + 1.) / 2.)
+}
+
+// roots of the quadratic in the ray parameter, restricted to (t_min, t_max)
+fn quadratic_hits(a: f32, b: f32, c: f32, t_min: f32, t_max: f32) -> Vec<f32> {
+    solve_quadratic(a as f64, b as f64, c as f64)
+        .into_iter()
+        .map(|t| t as f32)
+        .filter(|t| *t > t_min && *t < t_max)
+        .collect()
+}
+
+// t at which the ray crosses the horizontal plane y = `height`, in local space
+fn hit_cap(origin: Vec3, direction: Vec3, height: f32, radius: f32, t_min: f32, t_max: f32) -> Option<f32> {
+    if direction.y.abs() < 1e-8 {
+        return None;
+    }
+    let t = (height - origin.y) / direction.y;
+    let p = origin + direction * t;
+    if t > t_min && t < t_max && p.x.powi(2) + p.z.powi(2) <= radius.powi(2) {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+// a potential hit: its ray parameter, normal and uv
+type Candidate = (f32, Vec3, (f32, f32));
+
+fn closest(a: Option<Candidate>, b: Option<Candidate>) -> Option<Candidate> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if a.0 < b.0 { a } else { b }),
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
+}
+
+fn to_hit_record<'a>(
+    ray: &Ray,
+    hit: Option<Candidate>,
+    material: &'a dyn Material,
+) -> Option<HitRecord<'a>> {
+    hit.map(|(t, normal, (u, v))| {
+        let tangent = around_y(normal);
+        HitRecord {
+            t,
+            point: ray.point_at_parameter(t),
+            normal,
+            tangent,
+            bitangent: up_y(normal, tangent),
+            u,
+            v,
+            material,
+        }
+    })
+}
+
+fn hit_cylinder<'a>(
+    center: Vec3,
+    (radius, height): (f32, f32),
+    capped: bool,
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let o = ray.origin - center;
+    let d = ray.direction;
+
+    let a = d.x.powi(2) + d.z.powi(2);
+    let b = 2. * (o.x * d.x + o.z * d.z);
+    let c = o.x.powi(2) + o.z.powi(2) - radius.powi(2);
+
+    let side = quadratic_hits(a, b, c, t_min, t_max)
+        .into_iter()
+        .map(|t| (t, o + d * t))
+        .find(|(_, p)| p.y >= 0. && p.y <= height)
+        .map(|(t, p)| (t, Vec3::new(p.x, 0., p.z) / radius, (azimuth_u(p), p.y / height)));
+
+    if !capped {
+        return to_hit_record(ray, side, material);
+    }
+
+    let bottom = hit_cap(o, d, 0., radius, t_min, t_max)
+        .map(|t| (t, Vec3::new(0., -1., 0.), cap_uv(o + d * t, radius)));
+    let top = hit_cap(o, d, height, radius, t_min, t_max)
+        .map(|t| (t, Vec3::new(0., 1., 0.), cap_uv(o + d * t, radius)));
+
+    to_hit_record(ray, closest(closest(side, bottom), top), material)
+}
+
+fn hit_cone<'a>(
+    center: Vec3,
+    (radius, height): (f32, f32),
+    capped: bool,
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let o = ray.origin - center;
+    let d = ray.direction;
+    // the radius of the cone at height y is k * (height - y)
+    let k = radius / height;
+    let k2 = k * k;
+
+    let a = d.x.powi(2) + d.z.powi(2) - k2 * d.y.powi(2);
+    let b = 2. * (o.x * d.x + o.z * d.z + k2 * (height - o.y) * d.y);
+    let c = o.x.powi(2) + o.z.powi(2) - k2 * (height - o.y).powi(2);
+
+    let side = quadratic_hits(a, b, c, t_min, t_max)
+        .into_iter()
+        .map(|t| (t, o + d * t))
+        .find(|(_, p)| p.y >= 0. && p.y <= height)
+        .map(|(t, p)| {
+            let normal = Vec3::new(p.x, k2 * (height - p.y), p.z).unit_vector();
+            (t, normal, (azimuth_u(p), p.y / height))
+        });
+
+    if !capped {
+        return to_hit_record(ray, side, material);
+    }
+
+    let bottom = hit_cap(o, d, 0., radius, t_min, t_max)
+        .map(|t| (t, Vec3::new(0., -1., 0.), cap_uv(o + d * t, radius)));
+
+    to_hit_record(ray, closest(side, bottom), material)
+}
+
+fn hit_paraboloid<'a>(
+    center: Vec3,
+    radius: f32,
+    height: f32,
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let o = ray.origin - center;
+    let d = ray.direction;
+    // x^2 + z^2 = k * y
+    let k = radius.powi(2) / height;
+
+    let a = d.x.powi(2) + d.z.powi(2);
+    let b = 2. * (o.x * d.x + o.z * d.z) - k * d.y;
+    let c = o.x.powi(2) + o.z.powi(2) - k * o.y;
+
+    let side = quadratic_hits(a, b, c, t_min, t_max)
+        .into_iter()
+        .map(|t| (t, o + d * t))
+        .find(|(_, p)| p.y >= 0. && p.y <= height)
+        .map(|(t, p)| {
+            let normal = Vec3::new(2. * p.x, -k, 2. * p.z).unit_vector();
+            (t, normal, (azimuth_u(p), p.y / height))
+        });
+
+    to_hit_record(ray, side, material)
+}
+
+fn hit_annulus<'a>(
+    center: Vec3,
+    normal: Vec3,
+    (inner_radius, outer_radius): (f32, f32),
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let denominator = normal.dot(ray.direction);
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+
+    let t = normal.dot(center - ray.origin) / denominator;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+
+    let point = ray.point_at_parameter(t);
+    let distance = (point - center).length();
+    if distance < inner_radius || distance > outer_radius {
+        return None;
+    }
+
+    let local = Onb::from_w(normal).to_local(point - center);
+    let phi = local.y.atan2(local.x);
+    let tangent = tangent_towards(normal, normal.cross(point - center));
+
+    Some(HitRecord {
+        t,
+        point,
+        normal,
+        tangent,
+        bitangent: bitangent_towards(normal, tangent, point - center),
+        u: (phi + PI) / (2. * PI),
+        v: (distance - inner_radius) / (outer_radius - inner_radius),
+        material,
+    })
+}
+
+fn hit_torus<'a>(
+    center: Vec3,
+    major_radius: f32,
+    minor_radius: f32,
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    // solve with a unit direction so the coefficients stay well scaled,
+    // then convert the distances back to the ray parameter.
+    let length = ray.direction.length() as f64;
+    let o = ray.origin - center;
+    let (ox, oy, oz) = (o.x as f64, o.y as f64, o.z as f64);
+    let (dx, dy, dz) = (
+        ray.direction.x as f64 / length,
+        ray.direction.y as f64 / length,
+        ray.direction.z as f64 / length,
+    );
+    let r2 = (major_radius as f64).powi(2);
+
+    // (|o + td|^2 + R^2 - r^2)^2 = 4R^2 ((ox + t dx)^2 + (oz + t dz)^2)
+    let h = 2. * (ox * dx + oy * dy + oz * dz);
+    let i = ox * ox + oy * oy + oz * oz + r2 - (minor_radius as f64).powi(2);
+    let j = dx * dx + dz * dz;
+    let k = 2. * (ox * dx + oz * dz);
+    let l = ox * ox + oz * oz;
+
+    let t = solve_quartic(
+        1.,
+        2. * h,
+        h * h + 2. * i - 4. * r2 * j,
+        2. * h * i - 4. * r2 * k,
+        i * i - 4. * r2 * l,
+    )
+        .into_iter()
+        .map(|t| (t / length) as f32)
+        .find(|t| *t > t_min && *t < t_max)?;
+
+    let point = ray.point_at_parameter(t);
+    let p = point - center;
+    let ring = Vec3::new(p.x, 0., p.z).unit_vector() * major_radius;
+    let normal = (p - ring) / minor_radius;
+    let tube_angle = p.y.atan2(Vec3::new(p.x, 0., p.z).length() - major_radius);
+    // u goes around the ring and v around the tube, even on its inner side
+    let tangent = around_y(ring);
+
+    Some(HitRecord {
+        t,
+        point,
+        normal,
+        tangent,
+        bitangent: tangent.cross(normal),
+        u: azimuth_u(p),
+        v: (tube_angle + PI) / (2. * PI),
+        material,
+    })
+}
+
+#[derive(Clone)]
+pub struct Cylinder {
+    pub center: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cylinder {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: Arc<dyn Material>) -> Self {
+        Cylinder {
+            center,
+            radius,
+            height,
+            capped: true,
+            material,
+        }
+    }
+
+    pub fn uncapped(center: Vec3, radius: f32, height: f32, material: Arc<dyn Material>) -> Self {
+        Cylinder {
+            center,
+            radius,
+            height,
+            capped: false,
+            material,
+        }
+    }
+}
+
+impl Hitable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_cylinder(
+            self.center,
+            (self.radius, self.height),
+            self.capped,
+            &*self.material,
+            ray,
+            t_min,
+            t_max
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(
+            Aabb {
+                min: self.center - Vec3::new(self.radius, 0., self.radius),
+                max: self.center + Vec3::new(self.radius, self.height, self.radius),
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Cone {
+    pub center: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub capped: bool,
+    pub material: Arc<dyn Material>,
+}
+
+impl Cone {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: Arc<dyn Material>) -> Self {
+        Cone {
+            center,
+            radius,
+            height,
+            capped: true,
+            material,
+        }
+    }
+
+    pub fn uncapped(center: Vec3, radius: f32, height: f32, material: Arc<dyn Material>) -> Self {
+        Cone {
+            center,
+            radius,
+            height,
+            capped: false,
+            material,
+        }
+    }
+}
+
+impl Hitable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_cone(
+            self.center,
+            (self.radius, self.height),
+            self.capped,
+            &*self.material,
+            ray,
+            t_min,
+            t_max
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(
+            Aabb {
+                min: self.center - Vec3::new(self.radius, 0., self.radius),
+                max: self.center + Vec3::new(self.radius, self.height, self.radius),
+            }
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct Paraboloid {
+    pub center: Vec3,
+    pub radius: f32,
+    pub height: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl Paraboloid {
+    pub fn new(center: Vec3, radius: f32, height: f32, material: Arc<dyn Material>) -> Self {
+        Paraboloid {
+            center,
+            radius,
+            height,
+            material,
+        }
+    }
+}
+
+impl Hitable for Paraboloid {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_paraboloid(
+            self.center,
+            self.radius,
+            self.height,
+            &*self.material,
+            ray,
+            t_min,
+            t_max
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(
+            Aabb {
+                min: self.center - Vec3::new(self.radius, 0., self.radius),
+                max: self.center + Vec3::new(self.radius, self.height, self.radius),
+            }
+        )
+    }
+}
+
+// the box of a flat ring. It is padded a little along the normal so the
+// slabs test never sees a zero-thickness box.
+fn annulus_bounding_box(center: Vec3, normal: Vec3, radius: f32) -> Aabb {
+    let extent = Vec3::new(
+        radius * (1. - normal.x.powi(2)).max(0.).sqrt(),
+        radius * (1. - normal.y.powi(2)).max(0.).sqrt(),
+        radius * (1. - normal.z.powi(2)).max(0.).sqrt(),
+    ) + 0.0001;
+
+    Aabb {
+        min: center - extent,
+        max: center + extent,
+    }
+}
+
+// Density, in solid angle, of `annulus_random` picking `direction`: the
+// uniform density over the area, seen from `origin`.
+fn annulus_pdf_value<H: Hitable>(annulus: &H, normal: Vec3, area: f32, origin: Vec3, direction: Vec3) -> f32 {
+    let ray = Ray { origin, direction, time: 0. };
+    match annulus.hit(&ray, 0.001, f32::MAX) {
+        Some(rec) => {
+            let distance_squared = rec.t.powi(2) * direction.squared_length();
+            let cosine = (normal.dot(direction) / direction.length()).abs();
+            distance_squared / (cosine * area)
+        },
+        None => 0.,
+    }
+}
+
+// direction from `origin` towards a point drawn uniformly on the annulus
+fn annulus_random(center: Vec3, normal: Vec3, (inner_radius, outer_radius): (f32, f32), origin: Vec3) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let r = (inner_radius.powi(2) + rng.gen::<f32>() * (outer_radius.powi(2) - inner_radius.powi(2))).sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    let frame = Onb::from_w(normal);
+    center + frame.local(Vec3::new(r * phi.cos(), r * phi.sin(), 0.)) - origin
+}
+
+#[derive(Clone)]
+pub struct Disk {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub radius: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl Disk {
+    pub fn new(center: Vec3, normal: Vec3, radius: f32, material: Arc<dyn Material>) -> Self {
+        Disk {
+            center,
+            normal: normal.unit_vector(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hitable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_annulus(
+            self.center,
+            self.normal,
+            (0., self.radius),
+            &*self.material,
+            ray,
+            t_min,
+            t_max
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(annulus_bounding_box(self.center, self.normal, self.radius))
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let area = PI * self.radius.powi(2);
+        annulus_pdf_value(self, self.normal, area, origin, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        annulus_random(self.center, self.normal, (0., self.radius), origin)
+    }
+}
+
+#[derive(Clone)]
+pub struct Annulus {
+    pub center: Vec3,
+    pub normal: Vec3,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl Annulus {
+    pub fn new(center: Vec3, normal: Vec3, inner_radius: f32, outer_radius: f32, material: Arc<dyn Material>) -> Self {
+        Annulus {
+            center,
+            normal: normal.unit_vector(),
+            inner_radius,
+            outer_radius,
+            material,
+        }
+    }
+}
+
+impl Hitable for Annulus {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_annulus(
+            self.center,
+            self.normal,
+            (self.inner_radius, self.outer_radius),
+            &*self.material,
+            ray,
+            t_min,
+            t_max
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(annulus_bounding_box(self.center, self.normal, self.outer_radius))
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let area = PI * (self.outer_radius.powi(2) - self.inner_radius.powi(2));
+        annulus_pdf_value(self, self.normal, area, origin, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        annulus_random(self.center, self.normal, (self.inner_radius, self.outer_radius), origin)
+    }
+}
+
+// a torus lying in the xz plane, `center` being the center of its hole
+#[derive(Clone)]
+pub struct Torus {
+    pub center: Vec3,
+    pub major_radius: f32,
+    pub minor_radius: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, major_radius: f32, minor_radius: f32, material: Arc<dyn Material>) -> Self {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Hitable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_torus(
+            self.center,
+            self.major_radius,
+            self.minor_radius,
+            &*self.material,
+            ray,
+            t_min,
+            t_max
+        )
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        let extent = Vec3::new(
+            self.major_radius + self.minor_radius,
+            self.minor_radius,
+            self.major_radius + self.minor_radius,
+        );
+        Some(
+            Aabb {
+                min: self.center - extent,
+                max: self.center + extent,
+            }
+        )
+    }
+}
@@ -0,0 +1,235 @@
+// Axis aligned rectangles, mostly used as area lights and to build rooms.
+// Each one lies in the plane where the coordinate of its normal axis is `k`.
+
+extern crate rand;
+use self::rand::Rng;
+use std::sync::Arc;
+use std::f32;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, bitangent_towards};
+use material::{Material};
+
+// builds a point from its coordinate along the normal axis of the
+// rectangle and along the two other axes, in order
+fn from_axes(axis: usize, k: f32, a: f32, b: f32) -> Vec3 {
+    match axis {
+        0 => Vec3::new(k, a, b),
+        1 => Vec3::new(a, k, b),
+        _ => Vec3::new(a, b, k),
+    }
+}
+
+fn other_axes(axis: usize) -> (usize, usize) {
+    match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    }
+}
+
+// hits the rectangle of the plane where the coordinate `axis` is `k`
+fn hit_rect<'a>(
+    (axis, k): (usize, f32),
+    (a0, a1): (f32, f32),
+    (b0, b1): (f32, f32),
+    material: &'a dyn Material,
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<HitRecord<'a>> {
+    let (a_axis, b_axis) = other_axes(axis);
+    let t = (k - ray.origin[axis]) / ray.direction[axis];
+    if !(t > t_min && t < t_max) {
+        return None;
+    }
+
+    let point = ray.point_at_parameter(t);
+    let a = point[a_axis];
+    let b = point[b_axis];
+    if a < a0 || a > a1 || b < b0 || b > b1 {
+        return None;
+    }
+
+    Some(HitRecord {
+        t,
+        point,
+        normal: from_axes(axis, 1., 0., 0.),
+        tangent: from_axes(axis, 0., 1., 0.),
+        bitangent: bitangent_towards(from_axes(axis, 1., 0., 0.), from_axes(axis, 0., 1., 0.), from_axes(axis, 0., 0., 1.)),
+        u: (a - a0) / (a1 - a0),
+        v: (b - b0) / (b1 - b0),
+        material,
+    })
+}
+
+fn rect_bounding_box(axis: usize, k: f32, (a0, a1): (f32, f32), (b0, b1): (f32, f32)) -> Aabb {
+    // padded along the normal so the box never has a zero thickness
+    Aabb {
+        min: from_axes(axis, k - 0.0001, a0, b0),
+        max: from_axes(axis, k + 0.0001, a1, b1),
+    }
+}
+
+fn rect_pdf_value<H: Hitable>(rect: &H, axis: usize, area: f32, origin: Vec3, direction: Vec3) -> f32 {
+    let ray = Ray { origin, direction, time: 0. };
+    match rect.hit(&ray, 0.001, f32::MAX) {
+        Some(rec) => {
+            // converts the uniform density over the area to solid angle
+            let distance_squared = rec.t.powi(2) * direction.squared_length();
+            let cosine = (direction[axis] / direction.length()).abs();
+            distance_squared / (cosine * area)
+        },
+        None => 0.,
+    }
+}
+
+fn rect_random(axis: usize, k: f32, (a0, a1): (f32, f32), (b0, b1): (f32, f32), origin: Vec3) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let point = from_axes(
+        axis,
+        k,
+        a0 + rng.gen::<f32>() * (a1 - a0),
+        b0 + rng.gen::<f32>() * (b1 - b0),
+    );
+    point - origin
+}
+
+#[derive(Clone)]
+pub struct XyRect {
+    pub x0: f32,
+    pub x1: f32,
+    pub y0: f32,
+    pub y1: f32,
+    pub k: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl XyRect {
+    pub fn new(x0: f32, x1: f32, y0: f32, y1: f32, k: f32, material: Arc<dyn Material>) -> Self {
+        XyRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hitable for XyRect {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_rect((2, self.k), (self.x0, self.x1), (self.y0, self.y1), &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(rect_bounding_box(2, self.k, (self.x0, self.x1), (self.y0, self.y1)))
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let area = (self.x1 - self.x0) * (self.y1 - self.y0);
+        rect_pdf_value(self, 2, area, origin, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        rect_random(2, self.k, (self.x0, self.x1), (self.y0, self.y1), origin)
+    }
+}
+
+#[derive(Clone)]
+pub struct XzRect {
+    pub x0: f32,
+    pub x1: f32,
+    pub z0: f32,
+    pub z1: f32,
+    pub k: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl XzRect {
+    pub fn new(x0: f32, x1: f32, z0: f32, z1: f32, k: f32, material: Arc<dyn Material>) -> Self {
+        XzRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hitable for XzRect {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_rect((1, self.k), (self.x0, self.x1), (self.z0, self.z1), &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(rect_bounding_box(1, self.k, (self.x0, self.x1), (self.z0, self.z1)))
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let area = (self.x1 - self.x0) * (self.z1 - self.z0);
+        rect_pdf_value(self, 1, area, origin, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        rect_random(1, self.k, (self.x0, self.x1), (self.z0, self.z1), origin)
+    }
+}
+
+#[derive(Clone)]
+pub struct YzRect {
+    pub y0: f32,
+    pub y1: f32,
+    pub z0: f32,
+    pub z1: f32,
+    pub k: f32,
+    pub material: Arc<dyn Material>,
+}
+
+impl YzRect {
+    pub fn new(y0: f32, y1: f32, z0: f32, z1: f32, k: f32, material: Arc<dyn Material>) -> Self {
+        YzRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hitable for YzRect {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        hit_rect((0, self.k), (self.y0, self.y1), (self.z0, self.z1), &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(rect_bounding_box(0, self.k, (self.y0, self.y1), (self.z0, self.z1)))
+    }
+
+    fn is_samplable(&self) -> bool {
+        true
+    }
+
+    fn pdf_value(&self, origin: Vec3, direction: Vec3) -> f32 {
+        let area = (self.y1 - self.y0) * (self.z1 - self.z0);
+        rect_pdf_value(self, 0, area, origin, direction)
+    }
+
+    fn random(&self, origin: Vec3) -> Vec3 {
+        rect_random(0, self.k, (self.y0, self.y1), (self.z0, self.z1), origin)
+    }
+}
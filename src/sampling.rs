@@ -0,0 +1,252 @@
+// Random directions and points for Monte Carlo integration. Each sampler comes
+// with the probability density it draws with, so estimators can divide by it.
+// Hemisphere samplers work in a local frame where z is the normal, use an
+// `Onb` to bring them to world space.
+
+extern crate rand;
+use self::rand::Rng;
+use std::f32::consts::PI;
+use vec3::{Vec3};
+
+// orthonormal basis, w usually being a surface normal
+#[derive(Debug, Copy, Clone)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    pub fn from_w(n: Vec3) -> Self {
+        let w = n.unit_vector();
+        let a =
+            if w.x.abs() > 0.9 { Vec3::new(0., 1., 0.) }
+            else { Vec3::new(1., 0., 0.) };
+        let v = w.cross(a).unit_vector();
+        // right-handed: u x v = w
+        let u = v.cross(w);
+        Onb { u, v, w }
+    }
+
+    // same, with u the direction of the plane of w closest to `direction`,
+    // or any of them when `direction` is along w
+    pub fn from_wu(n: Vec3, direction: Vec3) -> Self {
+        let w = n.unit_vector();
+        let u = direction - w * direction.dot(w);
+        if u.squared_length() <= 1e-12 {
+            return Onb::from_w(w);
+        }
+        let u = u.unit_vector();
+        Onb { u, v: w.cross(u), w }
+    }
+
+    // from local coordinates to world space
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+
+    // from world space to local coordinates
+    pub fn to_local(self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+}
+
+// rejection sampling in the [-1, 1] cube
+pub fn random_in_unit_sphere() -> Vec3 {
+    let mut rng = rand::thread_rng();
+    loop {
+        let p = 2. * Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::fromf(1.);
+        if p.squared_length() < 1. {
+            return p;
+        }
+    }
+}
+
+pub fn random_in_unit_sphere_pdf() -> f32 {
+    3. / (4. * PI)
+}
+
+// rejection sampling in the [-1, 1] square, z is always 0
+pub fn random_in_unit_disk() -> Vec3 {
+    let mut rng = rand::thread_rng();
+    loop {
+        let p = 2. * Vec3::new(rng.gen(), rng.gen(), 0.) - Vec3::new(1., 1., 0.);
+        if p.squared_length() < 1. {
+            return p;
+        }
+    }
+}
+
+pub fn uniform_sphere() -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let z = 1. - 2. * rng.gen::<f32>();
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_sphere_pdf() -> f32 {
+    1. / (4. * PI)
+}
+
+pub fn uniform_hemisphere() -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let z = rng.gen::<f32>();
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_hemisphere_pdf() -> f32 {
+    1. / (2. * PI)
+}
+
+// directions with a density proportional to the cosine with the z axis,
+// by projecting uniform points of the disk on the hemisphere (Malley's method)
+pub fn cosine_hemisphere() -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let r = rng.gen::<f32>().sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    let x = r * phi.cos();
+    let y = r * phi.sin();
+    Vec3::new(x, y, (1. - x * x - y * y).max(0.).sqrt())
+}
+
+pub fn cosine_hemisphere_pdf(cos_theta: f32) -> f32 {
+    cos_theta.max(0.) / PI
+}
+
+// directions inside the cone around the z axis whose half angle has
+// a cosine of `cos_theta_max`
+pub fn uniform_cone(cos_theta_max: f32) -> Vec3 {
+    let mut rng = rand::thread_rng();
+    let z = 1. - rng.gen::<f32>() * (1. - cos_theta_max);
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = 2. * PI * rng.gen::<f32>();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+pub fn uniform_cone_pdf(cos_theta_max: f32) -> f32 {
+    1. / (2. * PI * (1. - cos_theta_max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: usize = 200000;
+
+    // Checks that the mean of the values matches `expected` within five
+    // standard errors.
+    fn assert_mean<I: Iterator<Item = f32>>(values: I, expected: f32) {
+        let values: Vec<f64> = values.map(|x| x as f64).collect();
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let variance = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.);
+        let tolerance = 5. * (variance / n).sqrt() + 1e-6;
+        assert!(
+            (mean - expected as f64).abs() < tolerance,
+            "mean {} is not within {} of {}", mean, tolerance, expected
+        );
+    }
+
+    // the integral of a density over the sphere of directions, estimated
+    // with uniform directions
+    fn assert_normalized<F: Fn(Vec3) -> f32>(pdf: F) {
+        assert_mean((0..SAMPLES).map(|_| pdf(uniform_sphere()) / uniform_sphere_pdf()), 1.);
+    }
+
+    #[test]
+    fn onb_is_right_handed_and_orthonormal() {
+        let normals = [
+            Vec3::new(0., 0., 1.),
+            Vec3::new(1., 0., 0.),
+            Vec3::new(0., -1., 0.),
+            Vec3::new(-0.3, 0.5, 0.8),
+            Vec3::new(0.95, -0.1, 0.2),
+        ];
+        let direction = Vec3::new(1., 0.5, 0.);
+        for normal in normals.iter() {
+            let frame = Onb::from_wu(*normal, direction);
+            assert!(frame.u.dot(direction) > 0.);
+            assert!(frame.u.dot(*normal).abs() < 1e-5);
+            assert!((frame.u.cross(frame.v) - frame.w).length() < 1e-5);
+        }
+        for normal in normals.iter() {
+            let frame = Onb::from_w(*normal);
+            assert!((frame.u.cross(frame.v) - frame.w).length() < 1e-5);
+            assert!((frame.w - normal.unit_vector()).length() < 1e-5);
+            assert!(frame.u.dot(frame.v).abs() < 1e-5);
+            assert!((frame.u.length() - 1.).abs() < 1e-5);
+            assert!((frame.v.length() - 1.).abs() < 1e-5);
+            let a = Vec3::new(0.2, -0.7, 0.4);
+            assert!((frame.local(frame.to_local(a)) - a).length() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn uniform_sphere_is_uniform() {
+        let samples: Vec<Vec3> = (0..SAMPLES).map(|_| uniform_sphere()).collect();
+        assert!(samples.iter().all(|d| (d.length() - 1.).abs() < 1e-4));
+        assert_mean(samples.iter().map(|d| d.z), 0.);
+        assert_mean(samples.iter().map(|d| d.x), 0.);
+        assert_mean(samples.iter().map(|d| d.z * d.z), 1. / 3.);
+        assert_normalized(|_| uniform_sphere_pdf());
+    }
+
+    #[test]
+    fn uniform_hemisphere_is_uniform() {
+        let samples: Vec<Vec3> = (0..SAMPLES).map(|_| uniform_hemisphere()).collect();
+        assert!(samples.iter().all(|d| (d.length() - 1.).abs() < 1e-4 && d.z >= 0.));
+        assert_mean(samples.iter().map(|d| d.z), 0.5);
+        assert_mean(samples.iter().map(|d| d.y), 0.);
+        assert_normalized(|d| if d.z > 0. { uniform_hemisphere_pdf() } else { 0. });
+    }
+
+    #[test]
+    fn cosine_hemisphere_follows_the_cosine() {
+        let samples: Vec<Vec3> = (0..SAMPLES).map(|_| cosine_hemisphere()).collect();
+        assert!(samples.iter().all(|d| (d.length() - 1.).abs() < 1e-4 && d.z >= 0.));
+        assert_mean(samples.iter().map(|d| d.z), 2. / 3.);
+        assert_mean(samples.iter().map(|d| d.z * d.z), 0.5);
+        assert_mean(samples.iter().map(|d| d.x), 0.);
+        assert_normalized(|d| cosine_hemisphere_pdf(d.z));
+    }
+
+    #[test]
+    fn uniform_cone_stays_in_the_cone() {
+        for &cos_theta_max in [0.9, 0.5, -0.2].iter() {
+            let samples: Vec<Vec3> = (0..SAMPLES).map(|_| uniform_cone(cos_theta_max)).collect();
+            assert!(samples.iter().all(|d| (d.length() - 1.).abs() < 1e-4 && d.z >= cos_theta_max - 1e-6));
+            assert_mean(samples.iter().map(|d| d.z), (1. + cos_theta_max) / 2.);
+            assert_mean(samples.iter().map(|d| d.x), 0.);
+            assert_normalized(|d| if d.z >= cos_theta_max { uniform_cone_pdf(cos_theta_max) } else { 0. });
+        }
+    }
+
+    #[test]
+    fn random_in_unit_sphere_fills_the_ball() {
+        let samples: Vec<Vec3> = (0..SAMPLES).map(|_| random_in_unit_sphere()).collect();
+        assert!(samples.iter().all(|p| p.squared_length() < 1.));
+        assert_mean(samples.iter().map(|p| p.squared_length()), 3. / 5.);
+        assert_mean(samples.iter().map(|p| p.y), 0.);
+        // the density integrated over the [-1, 1] cube, which has a volume of 8
+        let mut rng = rand::thread_rng();
+        assert_mean(
+            (0..SAMPLES).map(|_| {
+                let p = 2. * Vec3::new(rng.gen(), rng.gen(), rng.gen()) - Vec3::fromf(1.);
+                if p.squared_length() < 1. { 8. * random_in_unit_sphere_pdf() } else { 0. }
+            }),
+            1.,
+        );
+    }
+
+    #[test]
+    fn random_in_unit_disk_fills_the_disk() {
+        let samples: Vec<Vec3> = (0..SAMPLES).map(|_| random_in_unit_disk()).collect();
+        assert!(samples.iter().all(|p| p.squared_length() < 1. && p.z == 0.));
+        assert_mean(samples.iter().map(|p| p.squared_length()), 0.5);
+        assert_mean(samples.iter().map(|p| p.x), 0.);
+        assert_mean(samples.iter().map(|p| p.x * p.x), 0.25);
+    }
+}
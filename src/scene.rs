@@ -0,0 +1,92 @@
+use rand::{self, Rng};
+use std::sync::Arc;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{Hitable, HitRecord};
+use bvh_node::{BvhTree};
+use light::{Light, LightSample};
+use environment::{Environment, EnvironmentLight};
+
+// Everything an integrator needs to render: the geometry, the lights to
+// sample, and the color of the rays escaping the scene.
+pub struct Scene<'a> {
+    pub world: BvhTree<'a>,
+    // every emitter of the world has to be in there, integrators that
+    // sample lights ignore the emission they hit by chance. That includes
+    // the background when it's lit by an environment light.
+    pub lights: Vec<Box<dyn Light>>,
+    pub background: Box<dyn Fn(&Ray) -> Vec3>,
+}
+
+impl<'a> Scene<'a> {
+    pub fn new(world: BvhTree<'a>, lights: Vec<Box<dyn Light>>, background: Box<dyn Fn(&Ray) -> Vec3>) -> Self {
+        Scene {
+            world,
+            lights,
+            background,
+        }
+    }
+
+    // a scene lit by `environment`, which is also its background
+    pub fn with_environment(world: BvhTree<'a>, mut lights: Vec<Box<dyn Light>>, environment: Arc<dyn Environment>) -> Self {
+        let (width, height) = environment.resolution();
+        lights.push(Box::new(EnvironmentLight::new(environment.clone(), width, height)));
+        let background = move |r: &Ray| environment.radiance(r.direction.unit_vector());
+        Scene::new(world, lights, Box::new(background))
+    }
+
+    pub fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        self.world.hit(r, t_min, t_max)
+    }
+
+    // samples one of the lights, picked uniformly
+    pub fn sample_light(&self, point: Vec3, time: f32) -> Option<LightSample> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let index = rand::thread_rng().gen_range(0, self.lights.len());
+        self.lights[index].sample(point, time).map(|sample| LightSample {
+            pdf: sample.pdf / self.lights.len() as f32,
+            ..sample
+        })
+    }
+
+    // Density `sample_light` would pick `direction` with, counting only the
+    // lights that can be hit along it: emitters of the world when a surface
+    // is hit, or environment lights when the ray escapes. The others would
+    // be blocked.
+    pub fn light_pdf(&self, point: Vec3, direction: Vec3, escaped: bool) -> f32 {
+        if self.lights.is_empty() {
+            return 0.;
+        }
+        self.lights.iter()
+            .filter(|light| light.is_environment() == escaped)
+            .map(|light| light.pdf(point, direction))
+            .sum::<f32>() / self.lights.len() as f32
+    }
+
+    // Whether `rec`, found along `ray`, is on one of the lights, whose
+    // emission `sample_light` accounts for. Other emitters, like glowing
+    // media, can only be found by the bounces.
+    pub fn is_light(&self, ray: &Ray, rec: &HitRecord) -> bool {
+        self.lights.iter().any(|light| light.is_hit(ray, rec))
+    }
+
+    // whether the light of the background is sampled by `sample_light`
+    pub fn has_environment_light(&self) -> bool {
+        self.lights.iter().any(|light| light.is_environment())
+    }
+
+    // whether something is between `point` and `distance` along `direction`
+    pub fn occluded(&self, point: Vec3, direction: Vec3, distance: f32, time: f32) -> bool {
+        let ray = Ray { origin: point, direction, time };
+        self.world.hit(&ray, 0.01, distance - 0.01).is_some()
+    }
+
+    // fraction of the light going from `point` to `distance` along
+    // `direction`, or the other way around
+    pub fn transmittance(&self, point: Vec3, direction: Vec3, distance: f32, time: f32) -> f32 {
+        let ray = Ray { origin: point, direction, time };
+        self.world.transmittance(&ray, 0.01, distance - 0.01)
+    }
+}
@@ -0,0 +1,885 @@
+// The example scenes, picked by name with --scene. Each one gives the
+// objects, the lights to sample, and what the rays escaping it see.
+
+use rand::{self, Rng};
+use std::io;
+use std::sync::Arc;
+use vec3::{Vec3};
+use ray::{Ray};
+use hitable::{Hitable, HitableList, Sphere, MovingSphere};
+use material::{Material, Lambertian, Metal, Dielectric, DiffuseLight, Isotropic, HenyeyGreenstein, MixMaterial, CoatedMaterial};
+use bvh_node::{BvhTree};
+use texture::{Texture, ConstantTexture, CheckedTexture, NoiseTexture, TurbulenceTexture, ImageTexture};
+use rect::{XyRect, XzRect, YzRect};
+use quadrics::{Cylinder, Cone, Paraboloid, Disk, Annulus, Torus};
+use cuboid::{Cuboid};
+use csg::{Csg};
+use sdf::{Sdf, SdfObject, RoundedBox, Capsule, Torus as SdfTorus, Translate, SmoothUnion, SmoothSubtraction, Repeat, Displace};
+use aabb::{Aabb};
+use heightfield::{Heightfield};
+use noises::{Perlin};
+use light::{Light, AreaLight, PointLight, SpotLight, DirectionalLight, SunLight};
+use scene::{Scene};
+use environment::{Environment};
+use sky::{self, PreethamSky};
+use medium::{ConstantMedium, HeterogeneousMedium, NoiseDensity, VoxelGrid};
+use microfacet::{RoughConductor, RoughDielectric};
+use principled::{Principled};
+use bump::{BumpMap, NormalMap};
+use mesh::{Mesh};
+use subsurface::{Subsurface};
+use diffuse::{OrenNayar, Sheen};
+
+pub enum Background {
+    // the white to blue gradient of the original renders
+    Gradient,
+    Black,
+    // also sampled as a light
+    Environment(Arc<dyn Environment>),
+}
+
+impl Background {
+    pub fn scene<'a>(self, world: BvhTree<'a>, lights: Vec<Box<dyn Light>>) -> Scene<'a> {
+        match self {
+            Background::Gradient => Scene::new(world, lights, Box::new(gradient)),
+            Background::Black => Scene::new(world, lights, Box::new(|_: &Ray| Vec3::zero())),
+            Background::Environment(environment) => Scene::with_environment(world, lights, environment),
+        }
+    }
+}
+
+pub type SceneParts = (HitableList, Vec<Box<dyn Light>>, Background);
+
+fn gradient(r: &Ray) -> Vec3 {
+    let unit_direction = r.direction.unit_vector();
+    let t = 0.5 * (unit_direction.y + 1.);
+    Vec3::new(1., 1., 1.) * (1. - t) + Vec3::new(0.5, 0.7, 1.) * t
+}
+
+fn random_spheres() -> SceneParts {
+    let beige = Vec3::new(246., 211., 195.) / 255.;
+    let brown = Vec3::new(163., 82., 51.) / 255.;
+
+    let colors = [
+        Vec3::new(139., 75., 98.) / 255.,
+        Vec3::new(187., 111., 107.) / 255.,
+        Vec3::new(234., 150., 116.) / 255.,
+        Vec3::new(252., 188., 128.) / 255.,
+        Vec3::new(247., 226., 156.) / 255.,
+        beige,
+        brown,
+    ];
+
+    let mut world : HitableList;
+
+    let intersects_with_main_spheres = |new_center: Vec3, new_radius: f32| {
+        [
+            (Vec3::new(0., 0., -1.), 0.7),
+            (Vec3::new(-1., 0., -1.), 0.7),
+            (Vec3::new(1., 0., -1.), 0.7),
+        ].iter().fold(false, |acc, (center, radius)| {
+            if acc { return acc };
+            let distance = (new_center - center).length();
+            distance < new_radius || distance < *radius
+        })
+    };
+
+    let random_color_and_position = || {
+        let mut rng = rand::thread_rng();
+        let mut x : f32;
+        let mut z : f32;
+        loop {
+            x = rng.gen_range::<f32>(-5., 5.);
+            z = rng.gen_range::<f32>(-5., 5.);
+            if !intersects_with_main_spheres(Vec3::new(x, -0.3, z), 0.2) { break; }
+        }
+
+        (
+            &colors[rng.gen_range::<u64>(0, colors.len() as u64) as usize],
+            x,
+            z,
+        )
+    };
+
+    world = vec![
+        Box::new(Sphere::new(
+            Vec3::new(0., -100.5 , -1.),
+            100.0,
+            Arc::new(
+                Lambertian::new(
+                    Box::new(
+                        CheckedTexture::new(
+                            Box::new(ConstantTexture::new(Vec3::new(0.8, 0.8, 0.8))),
+                            Box::new(ConstantTexture::new(Vec3::new(0.2, 0.2, 0.2)))
+                        )
+                    )
+                )
+            ),
+        )),
+        Box::new(Sphere::new(
+            Vec3::new(0., 0., -1.),
+            0.5,
+            Arc::new(
+                Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(139., 75., 98.) / 255.)))
+            ),
+        )),
+        Box::new(Sphere::new(
+            Vec3::new(-1., 0., -1.),
+            0.5,
+            Arc::new(
+                Dielectric::new(1.5)
+            ),
+        )),
+        Box::new(Sphere::new(
+            Vec3::new(1., 0., -1.),
+            0.5,
+            Arc::new(
+                Metal::new(beige, 0.)
+            ),
+        )),
+    ];
+
+    for _ in 0..50 {
+        let (color, x, z) = random_color_and_position();
+        let center = Vec3::new(x, -0.3, z);
+        let center_delta_y = rand::thread_rng().gen::<f32>() / 2.;
+        world.push(Box::new(
+            MovingSphere::new(
+                center,
+                center + Vec3::new(0., center_delta_y, 0.),
+                0.,
+                1.,
+                0.2,
+                Arc::new(
+                    Lambertian::new(Box::new(ConstantTexture::new(*color)))
+                )
+            )
+        ));
+    }
+
+    for _ in 0..25 {
+        let (color, x, z) = random_color_and_position();
+        let fuzz = rand::thread_rng().gen::<f32>();
+        world.push(Box::new(
+            Sphere::new(
+                Vec3::new(x, -0.3, z),
+                0.2,
+                Arc::new(
+                    Metal::new(*color, fuzz)
+                )
+            )
+        ));
+    }
+
+    for _ in 0..15 {
+        let (_, x, z) = random_color_and_position();
+        world.push(Box::new(
+            Sphere::new(
+                Vec3::new(x, -0.3, z),
+                0.2,
+                Arc::new(
+                    Dielectric::new(1.5)
+                )
+            )
+        ));
+    }
+
+    (world, vec![], Background::Gradient)
+}
+
+fn two_spheres() -> SceneParts {
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(2.))))
+            )
+        ),
+        Box::new(
+           Sphere::new(
+                Vec3::new(0., 2., 0.),
+                2.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(2.))))
+            )
+        )
+    ];
+    (world, vec![], Background::Gradient)
+}
+
+// the analytic shapes other than spheres
+fn quadrics() -> SceneParts {
+    let matte = |color: Vec3| -> Arc<dyn Material> { Arc::new(Lambertian::new(Box::new(ConstantTexture::new(color)))) };
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        // a tower with a pointed roof
+        Box::new(Cylinder::new(Vec3::new(0., 0., 3.6), 0.6, 1.4, matte(Vec3::new(0.7, 0.2, 0.1)))),
+        Box::new(Cone::new(Vec3::new(0., 1.4, 3.6), 0.75, 0.6, matte(Vec3::new(0.3, 0.3, 0.35)))),
+        // standing on the ground, its base can't be seen
+        Box::new(Cone::uncapped(Vec3::new(0., 0., 1.8), 0.7, 1.8, matte(Vec3::new(0.2, 0.5, 0.2)))),
+        Box::new(Paraboloid::new(Vec3::new(0., 0.1, 0.), 0.8, 1.6, Arc::new(Metal::new(Vec3::new(0.9, 0.8, 0.5), 0.1)))),
+        Box::new(Torus::new(Vec3::new(0., 0.25, -1.8), 0.6, 0.25, matte(Vec3::new(0.1, 0.3, 0.7)))),
+        // a pipe, seen through its open ends
+        Box::new(Cylinder::uncapped(Vec3::new(0., 0., -3.6), 0.6, 1.2, Arc::new(Metal::new(Vec3::fromf(0.8), 0.3)))),
+        Box::new(Annulus::new(Vec3::new(-3., 1.5, 0.), Vec3::new(1., 0.1, 0.), 0.8, 1.4, matte(Vec3::new(0.8, 0.7, 0.2)))),
+    ];
+    (world, vec![], Background::Gradient)
+}
+
+// solids carved out of each other
+fn csg() -> SceneParts {
+    let matte = |color: Vec3| -> Arc<dyn Material> { Arc::new(Lambertian::new(Box::new(ConstantTexture::new(color)))) };
+
+    // a rounded cube with a hole through it
+    let rounded_cube = Csg::intersection(
+        Box::new(Cuboid::new(Vec3::new(-0.8, 0., 1.7), Vec3::new(0.8, 1.6, 3.3), matte(Vec3::new(0.7, 0.2, 0.1)))),
+        Box::new(Sphere::new(Vec3::new(0., 0.8, 2.5), 1.05, matte(Vec3::new(0.8, 0.7, 0.2)))),
+    );
+    let pierced_cube = Csg::difference(
+        Box::new(rounded_cube),
+        Box::new(Cylinder::new(Vec3::new(0., -0.1, 2.5), 0.45, 1.8, matte(Vec3::new(0.2, 0.5, 0.2)))),
+    );
+    // a sphere with a groove around it, on a cone
+    let grooved_sphere = Csg::union(
+        Box::new(Csg::difference(
+            Box::new(Sphere::new(Vec3::new(0., 1.4, 0.), 0.8, Arc::new(Metal::new(Vec3::fromf(0.8), 0.2)))),
+            Box::new(Torus::new(Vec3::new(0., 1.4, 0.), 0.8, 0.2, matte(Vec3::new(0.1, 0.3, 0.7)))),
+        )),
+        Box::new(Cone::new(Vec3::new(0., 0., 0.), 0.5, 1., matte(Vec3::new(0.1, 0.3, 0.7)))),
+    );
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(pierced_cube),
+        Box::new(grooved_sphere),
+        Box::new(Cuboid::new(Vec3::new(-0.7, 0., -3.2), Vec3::new(0.7, 1.4, -1.8), Arc::new(Dielectric::new(1.5)))),
+    ];
+    (world, vec![], Background::Gradient)
+}
+
+// shapes given by signed distance functions, blended, carved, repeated and
+// displaced
+fn distance_fields() -> SceneParts {
+    let matte = |color: Vec3| -> Arc<dyn Material> { Arc::new(Lambertian::new(Box::new(ConstantTexture::new(color)))) };
+    let bounds = |min: Vec3, max: Vec3| Aabb { min, max };
+    let ball = |center: Vec3, radius: f32| -> Box<dyn Sdf> { Box::new(move |p: Vec3| (p - center).length() - radius) };
+
+    // a ring melting into a rounded box
+    let blob = SmoothUnion::new(
+        Box::new(Translate::new(Box::new(RoundedBox::new(Vec3::new(0.5, 0.4, 0.5), 0.1)), Vec3::new(0., 0.4, 2.5))),
+        Box::new(Translate::new(Box::new(SdfTorus::new(0.6, 0.15)), Vec3::new(0., 1., 2.5))),
+        0.2,
+    );
+    // a rounded box hollowed by a ball
+    let carved = SmoothSubtraction::new(
+        Box::new(Translate::new(Box::new(RoundedBox::new(Vec3::fromf(0.6), 0.15)), Vec3::new(0., 0.6, 0.))),
+        ball(Vec3::new(0.6, 1.2, 0.6), 0.7),
+        0.1,
+    );
+    // the noise overestimates the distance, so march slower
+    let rock = SdfObject {
+        step_scale: 0.5,
+        ..SdfObject::new(
+            Box::new(Displace::new(ball(Vec3::new(0., 0.7, -2.5), 0.6), 3., 0.2)),
+            bounds(Vec3::new(-1., -0.1, -3.5), Vec3::new(1., 1.6, -1.5)),
+            matte(Vec3::new(0.6, 0.55, 0.5)),
+        )
+    };
+    // a row of posts behind the rest
+    let fence = Repeat::new(
+        Box::new(Capsule::new(Vec3::new(-3., 0., 0.), Vec3::new(-3., 1.2, 0.), 0.1)),
+        Vec3::new(0., 0., 0.6),
+    );
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(SdfObject::new(
+            Box::new(blob),
+            bounds(Vec3::new(-1., -0.1, 1.5), Vec3::new(1., 1.4, 3.5)),
+            Arc::new(Metal::new(Vec3::new(0.9, 0.8, 0.5), 0.1)),
+        )),
+        Box::new(SdfObject::new(
+            Box::new(carved),
+            bounds(Vec3::new(-1., -0.1, -1.), Vec3::new(1., 1.4, 1.)),
+            matte(Vec3::new(0.7, 0.2, 0.1)),
+        )),
+        Box::new(rock),
+        Box::new(SdfObject::new(
+            Box::new(fence),
+            bounds(Vec3::new(-3.2, -0.2, -4.), Vec3::new(-2.8, 1.4, 4.)),
+            matte(Vec3::new(0.3, 0.2, 0.1)),
+        )),
+    ];
+    (world, vec![], Background::Gradient)
+}
+
+// Hills of perlin turbulence behind a relief carved from the marble render
+// of the readme. Run it from the root of the repository.
+fn terrain() -> SceneParts {
+    let grass = Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0.25, 0.4, 0.15)))));
+    let stone = Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0.7, 0.65, 0.6)))));
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000.01, 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(Heightfield::from_perlin(
+            &Perlin::new(),
+            0.5,
+            256,
+            128,
+            Vec3::new(-10., 0., 0.5),
+            Vec3::new(14., 1.2, 6.),
+            grass,
+        )),
+        Box::new(Heightfield::from_image(
+            "images/img-marble.jpg",
+            Vec3::new(-2., 0., -4.5),
+            Vec3::new(4., 0.3, 4.5),
+            stone,
+        )),
+    ];
+    (world, vec![], Background::Gradient)
+}
+
+// a rectangle and a sphere of light, in the dark
+fn simple_light() -> SceneParts {
+    let rect_light = XyRect::new(3., 5., 1., 3., -2., Arc::new(
+        DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(4.))))
+    ));
+    let sphere_light = Sphere::new(Vec3::new(0., 7., 0.), 2., Arc::new(
+        DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(4.))))
+    ));
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.))))
+            )
+        ),
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., 2., 0.),
+                2.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.))))
+            )
+        ),
+        Box::new(rect_light.clone()),
+        Box::new(sphere_light.clone()),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![
+        Box::new(AreaLight::new(Box::new(rect_light))),
+        Box::new(AreaLight::new(Box::new(sphere_light))),
+    ];
+
+    (world, lights, Background::Black)
+}
+
+// a box with colored side walls, lit by a panel in its ceiling and open
+// towards the camera
+fn cornell_box() -> SceneParts {
+    let matte = |color: Vec3| -> Arc<dyn Material> { Arc::new(Lambertian::new(Box::new(ConstantTexture::new(color)))) };
+    let white = matte(Vec3::fromf(0.73));
+    let light = XzRect::new(-1.5, -0.5, 0.1, 1.1, 1.49, Arc::new(
+        DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(15.))))
+    ));
+
+    let world: HitableList = vec![
+        Box::new(YzRect::new(-1.5, 1.5, -2., 3.2, -2.5, white.clone())),
+        Box::new(XzRect::new(-2.5, 0.5, -2., 3.2, -1.5, white.clone())),
+        Box::new(XzRect::new(-2.5, 0.5, -2., 3.2, 1.5, white.clone())),
+        Box::new(XyRect::new(-2.5, 0.5, -1.5, 1.5, -2., matte(Vec3::new(0.65, 0.05, 0.05)))),
+        Box::new(XyRect::new(-2.5, 0.5, -1.5, 1.5, 3.2, matte(Vec3::new(0.12, 0.45, 0.15)))),
+        Box::new(light.clone()),
+        Box::new(Sphere::new(Vec3::new(-1.6, -0.9, 1.6), 0.6, Arc::new(Dielectric::new(1.5)))),
+        Box::new(Sphere::new(Vec3::new(-0.8, -1., -0.4), 0.5, white)),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(AreaLight::new(Box::new(light)))];
+
+    (world, lights, Background::Black)
+}
+
+// lights without geometry, in the dark
+fn punctual_lights() -> SceneParts {
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., 2., 0.),
+                2.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.))))
+            )
+        ),
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., 1., 3.),
+                1.,
+                Arc::new(Metal::new(Vec3::new(0.8, 0.6, 0.2), 0.3))
+            )
+        ),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![
+        Box::new(PointLight::new(Vec3::new(4., 5., -3.), Vec3::new(20., 16., 12.))),
+        Box::new(SpotLight::new(
+            Vec3::new(6., 6., 4.),
+            Vec3::new(-6., -6., -4.),
+            Vec3::new(10., 10., 40.),
+            25.,
+            15.,
+        )),
+        Box::new(SunLight::new(Vec3::new(-1., -2., -0.5), Vec3::fromf(1.), 0.53)),
+        // a dim blue fill from the other side, with sharp shadows
+        Box::new(DirectionalLight::new(Vec3::new(-0.5, -1., 1.), Vec3::new(0.05, 0.08, 0.2))),
+    ];
+
+    (world, lights, Background::Black)
+}
+
+// a few spheres under the sky
+fn daylight() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction_at(172, 16.5, 45.), 3., Vec3::fromf(0.3));
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.3)))))
+            )
+        ),
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., 2., 0.),
+                2.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.))))
+            )
+        ),
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., 1., 3.),
+                1.,
+                Arc::new(Dielectric::new(1.5))
+            )
+        ),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// a smoke ball and a light in a thin fog, in the dark
+fn foggy_light() -> SceneParts {
+    let light = XyRect::new(3., 5., 1., 3., -2., Arc::new(
+        DiffuseLight::new(Box::new(ConstantTexture::new(Vec3::fromf(8.))))
+    ));
+
+    let smoke = ConstantMedium::new(
+        Box::new(Sphere::new(Vec3::new(0., 2., 0.), 2., Arc::new(Dielectric::new(1.)))),
+        0.8,
+        Arc::new(Isotropic::new(Box::new(ConstantTexture::new(Vec3::new(0.8, 0.8, 0.9))))),
+    );
+    let fog = ConstantMedium::global(
+        100.,
+        0.03,
+        Arc::new(HenyeyGreenstein::new(Box::new(ConstantTexture::new(Vec3::fromf(0.9))), 0.6)),
+    );
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.))))
+            )
+        ),
+        Box::new(smoke),
+        Box::new(fog),
+        Box::new(light.clone()),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(AreaLight::new(Box::new(light)))];
+
+    (world, lights, Background::Black)
+}
+
+// A cloud lit by the sky, next to a fire. The fire isn't one of the lights,
+// so it needs an integrator that counts all the emission it finds, like
+// the mis or path ones.
+fn cloud_and_fire() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 200.), 2.5, Vec3::fromf(0.3));
+
+    let cloud = HeterogeneousMedium::new(
+        Box::new(Sphere::new(Vec3::new(0., 3., 0.), 2.5, Arc::new(Dielectric::new(1.)))),
+        Box::new(NoiseDensity::new(0.8, 4., 0.2)),
+        Arc::new(HenyeyGreenstein::new(Box::new(ConstantTexture::new(Vec3::fromf(0.95))), 0.5)),
+    );
+    // the flames are hotter where the smoke is thicker
+    let flames = Arc::new(NoiseDensity::new(2., 6., 0.3));
+    let fire = HeterogeneousMedium::emissive(
+        Box::new(Sphere::new(Vec3::new(0., 0.8, 3.), 0.8, Arc::new(Dielectric::new(1.)))),
+        Box::new(flames.clone()),
+        Arc::new(Isotropic::new(Box::new(ConstantTexture::new(Vec3::fromf(0.3))))),
+        Box::new(flames),
+        Vec3::new(4., 1.5, 0.3),
+    );
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.3)))))
+            )
+        ),
+        Box::new(cloud),
+        Box::new(fire),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// rough metals and glass under the sky
+fn rough_materials() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    let mut world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(CheckedTexture::new(
+                    Box::new(ConstantTexture::new(Vec3::fromf(0.2))),
+                    Box::new(ConstantTexture::new(Vec3::fromf(0.8))),
+                ))))
+            )
+        ),
+    ];
+
+    let materials: Vec<Arc<dyn Material>> = vec![
+        Arc::new(RoughConductor::gold(0.2)),
+        Arc::new(RoughConductor::copper(0.4)),
+        // brushed in patches
+        Arc::new(RoughConductor { roughness: Box::new(NoiseTexture::new(3.)), ..RoughConductor::aluminium(0.) }),
+        Arc::new(RoughDielectric::new(1.5, 0.1)),
+        Arc::new(RoughDielectric::new(1.5, 0.4)),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        world.push(Box::new(Sphere::new(Vec3::new(0., 1., 4.5 - 2.25 * i as f32), 1., material)));
+    }
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// the principled material's parameters one at a time, under the sky
+fn principled_materials() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+    let constant = |value: f32| -> Box<dyn Texture> { Box::new(ConstantTexture::new(Vec3::fromf(value))) };
+    let red = || -> Box<dyn Texture> { Box::new(ConstantTexture::new(Vec3::new(0.8, 0.1, 0.1))) };
+
+    let mut world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Principled::new(Box::new(NoiseTexture::new(4.))))
+            )
+        ),
+    ];
+
+    let materials: Vec<Arc<dyn Material>> = vec![
+        Arc::new(Principled::new(red())),
+        Arc::new(Principled { clearcoat: constant(1.), ..Principled::new(red()) }),
+        Arc::new(Principled { sheen: constant(1.), roughness: constant(0.9), ..Principled::new(red()) }),
+        Arc::new(Principled::metal(Box::new(ConstantTexture::new(Vec3::new(0.95, 0.64, 0.54))), constant(0.3))),
+        Arc::new(Principled::glass(constant(1.), constant(0.05), 1.5)),
+        Arc::new(Principled { emission: Box::new(ConstantTexture::new(Vec3::new(4., 2., 1.))), ..Principled::new(constant(0.)) }),
+    ];
+    for (i, material) in materials.into_iter().enumerate() {
+        world.push(Box::new(Sphere::new(Vec3::new(0., 1., 5. - 2. * i as f32), 0.9, material)));
+    }
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// rusty copper and varnished marble, under the sky
+fn layered_materials() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    let rust = Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0.45, 0.2, 0.08)))));
+    let rusty_copper = MixMaterial::new(Arc::new(RoughConductor::copper(0.25)), rust, Box::new(NoiseTexture::new(2.)));
+    let marble = Arc::new(Lambertian::new(Box::new(NoiseTexture::new(4.))));
+    let varnished_marble = CoatedMaterial::new(marble, 1.5);
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(Sphere::new(Vec3::new(0., 1., 1.5), 1., Arc::new(rusty_copper))),
+        Box::new(Sphere::new(Vec3::new(0., 1., -1.5), 1., Arc::new(varnished_marble))),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// Bumpy and scratched surfaces, and a tower of bricks from a normal map,
+// under the sky. Run it from the root of the repository.
+fn bumpy_materials() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    let plaster = Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0.8, 0.75, 0.7)))));
+    let hammered_copper = Arc::new(RoughConductor::copper(0.15));
+    let brick = Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0.6, 0.25, 0.15)))));
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(Sphere::new(Vec3::new(0., 1., 2.4), 1., Arc::new(
+            BumpMap::new(plaster, Box::new(NoiseTexture::new(1.)), 0.02)
+        ))),
+        Box::new(Cylinder::new(Vec3::zero(), 0.8, 1.6, Arc::new(
+            NormalMap::new(brick, Box::new(ImageTexture::new("images/normal-bricks.png")))
+        ))),
+        Box::new(Sphere::new(Vec3::new(0., 1., -2.4), 1., Arc::new(
+            BumpMap::new(hammered_copper, Box::new(NoiseTexture::new(8.)), 0.005)
+        ))),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// spheres with their surface displaced by turbulence, next to a bump mapped
+// one, under the sky
+fn displaced_spheres() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    let rock = Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::new(0.6, 0.55, 0.5)))));
+    let turbulence = TurbulenceTexture::new(2.);
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(
+            Mesh::sphere(Vec3::new(0., 1.2, 1.5), 1., 64, rock.clone())
+                .subdivided(1)
+                .displaced(&turbulence, 0.3)
+        ),
+        Box::new(Sphere::new(Vec3::new(0., 1.2, -1.5), 1., Arc::new(
+            BumpMap::new(rock, Box::new(TurbulenceTexture::new(2.)), 0.3)
+        ))),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// translucent marble, skin, milk and wax, a millimeter being a quarter of a
+// unit. The walks inside take hundreds of bounces: render with
+// `--max-volume 1000`.
+fn translucent_materials() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    let millimeter = 0.25;
+    let boundary = |center: Vec3, radius: f32| -> Box<dyn Hitable> {
+        Box::new(Sphere::new(center, radius, Arc::new(Lambertian::new(Box::new(ConstantTexture::fromf(0.5))))))
+    };
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(Subsurface::marble(boundary(Vec3::new(0., 0.7, 3.), 0.7), millimeter).with_roughness(0.05)),
+        Box::new(Subsurface::skin(boundary(Vec3::new(0., 0.7, 1.), 0.7), millimeter).with_roughness(0.3)),
+        Box::new(Subsurface::milk(boundary(Vec3::new(0., 0.7, -1.), 0.7), millimeter)),
+        Box::new(Subsurface::wax(boundary(Vec3::new(0., 0.7, -3.), 0.7), millimeter)),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// rough diffuse materials and velvet, under the sky
+fn fabric_materials() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    let moon = OrenNayar::new(Box::new(ConstantTexture::fromf(0.6)), 0.5);
+    let clay = OrenNayar::textured(
+        Box::new(ConstantTexture::new(Vec3::new(0.6, 0.3, 0.2))),
+        Box::new(NoiseTexture::new(4.)),
+    );
+    let red = || Box::new(ConstantTexture::new(Vec3::new(0.5, 0.02, 0.05)));
+    let velvet = MixMaterial::new(
+        Arc::new(Sheen::new(red(), 0.3)),
+        Arc::new(OrenNayar::new(red(), 0.3)),
+        Box::new(ConstantTexture::fromf(0.5)),
+    );
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(Sphere::new(Vec3::new(0., 1., 2.2), 1., Arc::new(moon))),
+        Box::new(Sphere::new(Vec3::new(0., 1., 0.), 1., Arc::new(clay))),
+        Box::new(Sphere::new(Vec3::new(0., 1., -2.2), 1., Arc::new(velvet))),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// brushed metals under the sky: spheres brushed around and from pole to
+// pole, and a spun disk whose highlights run along its radii
+fn brushed_metals() -> SceneParts {
+    let sky = PreethamSky::new(sky::sun_direction(35., 220.), 3., Vec3::fromf(0.3));
+
+    // turned a quarter, along the bitangents
+    let meridians = Box::new(ConstantTexture::new(Vec3::new(0.5, 1., 0.5)));
+    let copper = Principled {
+        anisotropic: Box::new(ConstantTexture::fromf(0.8)),
+        ..Principled::metal(Box::new(ConstantTexture::new(Vec3::new(0.95, 0.64, 0.54))), Box::new(ConstantTexture::fromf(0.3)))
+    };
+
+    let world: HitableList = vec![
+        Box::new(
+            Sphere::new(
+                Vec3::new(0., -1000., 0.),
+                1000.,
+                Arc::new(Lambertian::new(Box::new(ConstantTexture::new(Vec3::fromf(0.5)))))
+            )
+        ),
+        Box::new(Sphere::new(Vec3::new(0., 1., 2.2), 1., Arc::new(RoughConductor::aluminium(0.3).with_anisotropy(0.9)))),
+        Box::new(Sphere::new(
+            Vec3::new(0., 1., 0.),
+            1.,
+            Arc::new(RoughConductor::aluminium(0.3).with_anisotropy(0.9).with_tangents(meridians)),
+        )),
+        Box::new(Sphere::new(Vec3::new(0., 1., -2.2), 1., Arc::new(copper))),
+        Box::new(Disk::new(
+            Vec3::new(-3., 1.5, 0.),
+            Vec3::new(1., 0.1, 0.),
+            1.4,
+            Arc::new(RoughConductor::silver(0.25).with_alphas(
+                Box::new(ConstantTexture::fromf(0.02)),
+                Box::new(ConstantTexture::fromf(0.2)),
+            )),
+        )),
+    ];
+
+    let lights: Vec<Box<dyn Light>> = vec![Box::new(sky.sun_light())];
+
+    (world, lights, Background::Environment(Arc::new(sky)))
+}
+
+// smoke whose density is read from a voxel grid file, filling a cube of
+// 3 units standing at the center of the scenes
+pub fn smoke_from_file(path: &str) -> io::Result<Box<dyn Hitable>> {
+    let bounds = Aabb { min: Vec3::new(-1.5, 0., -1.5), max: Vec3::new(1.5, 3., 1.5) };
+    let grid = VoxelGrid::from_file(path, bounds)?;
+    Ok(Box::new(HeterogeneousMedium::new(
+        Box::new(Cuboid::new(bounds.min, bounds.max, Arc::new(Dielectric::new(1.)))),
+        Box::new(grid),
+        Arc::new(Isotropic::new(Box::new(ConstantTexture::fromf(0.8)))),
+    )))
+}
+
+type SceneConstructor = fn() -> SceneParts;
+
+// the scenes, by their name on the command line
+const SCENES: [(&str, SceneConstructor); 20] = [
+    ("random-spheres", random_spheres),
+    ("two-spheres", two_spheres),
+    ("quadrics", quadrics),
+    ("csg", csg),
+    ("distance-fields", distance_fields),
+    ("terrain", terrain),
+    ("simple-light", simple_light),
+    ("cornell-box", cornell_box),
+    ("punctual-lights", punctual_lights),
+    ("daylight", daylight),
+    ("foggy-light", foggy_light),
+    ("cloud-and-fire", cloud_and_fire),
+    ("rough-materials", rough_materials),
+    ("principled-materials", principled_materials),
+    ("layered-materials", layered_materials),
+    ("bumpy-materials", bumpy_materials),
+    ("displaced-spheres", displaced_spheres),
+    ("translucent-materials", translucent_materials),
+    ("fabric-materials", fabric_materials),
+    ("brushed-metals", brushed_metals),
+];
+
+pub fn scene_names() -> Vec<&'static str> {
+    SCENES.iter().map(|&(name, _)| name).collect()
+}
+
+pub fn scene_from_name(name: &str) -> Option<SceneParts> {
+    SCENES.iter().find(|&&(n, _)| n == name).map(|&(_, build)| build())
+}
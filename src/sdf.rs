@@ -0,0 +1,326 @@
+// Surfaces defined implicitly by a signed distance function: negative inside,
+// positive outside, and never more than the distance to the closest surface.
+// They are rendered by sphere tracing: we can always step along the ray by the
+// distance returned by the function without going through the surface.
+
+use std::sync::Arc;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord, get_sphere_uv, sphere_tangent, sphere_bitangent};
+use material::{Material};
+use noises::{Perlin};
+
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Vec3) -> f32;
+}
+
+// any closure can be used as a distance function
+impl<F: Fn(Vec3) -> f32 + Send + Sync> Sdf for F {
+    fn distance(&self, p: Vec3) -> f32 {
+        self(p)
+    }
+}
+
+fn vec_max(v: Vec3, m: f32) -> Vec3 {
+    Vec3::new(v.x.max(m), v.y.max(m), v.z.max(m))
+}
+
+fn vec_abs(v: Vec3) -> Vec3 {
+    Vec3::new(v.x.abs(), v.y.abs(), v.z.abs())
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1. - t) + b * t
+}
+
+// a box centered on the origin with rounded edges
+pub struct RoundedBox {
+    pub half_size: Vec3,
+    pub radius: f32,
+}
+
+impl RoundedBox {
+    pub fn new(half_size: Vec3, radius: f32) -> Self {
+        RoundedBox {
+            half_size,
+            radius,
+        }
+    }
+}
+
+impl Sdf for RoundedBox {
+    fn distance(&self, p: Vec3) -> f32 {
+        let q = vec_abs(p) - self.half_size + self.radius;
+        vec_max(q, 0.).length() + q.x.max(q.y.max(q.z)).min(0.) - self.radius
+    }
+}
+
+// a cylinder between `a` and `b` with spherical ends
+pub struct Capsule {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub radius: f32,
+}
+
+impl Capsule {
+    pub fn new(a: Vec3, b: Vec3, radius: f32) -> Self {
+        Capsule {
+            a,
+            b,
+            radius,
+        }
+    }
+}
+
+impl Sdf for Capsule {
+    fn distance(&self, p: Vec3) -> f32 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = (pa.dot(ba) / ba.dot(ba)).clamp(0., 1.);
+        (pa - ba * h).length() - self.radius
+    }
+}
+
+// a torus centered on the origin, lying in the xz plane
+pub struct Torus {
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl Torus {
+    pub fn new(major_radius: f32, minor_radius: f32) -> Self {
+        Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl Sdf for Torus {
+    fn distance(&self, p: Vec3) -> f32 {
+        let ring = Vec3::new(p.x, 0., p.z).length() - self.major_radius;
+        (ring.powi(2) + p.y.powi(2)).sqrt() - self.minor_radius
+    }
+}
+
+pub struct Translate {
+    pub sdf: Box<dyn Sdf>,
+    pub offset: Vec3,
+}
+
+impl Translate {
+    pub fn new(sdf: Box<dyn Sdf>, offset: Vec3) -> Self {
+        Translate {
+            sdf,
+            offset,
+        }
+    }
+}
+
+impl Sdf for Translate {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.sdf.distance(p - self.offset)
+    }
+}
+
+// union of two shapes, blended over a distance of `k` where they meet
+pub struct SmoothUnion {
+    pub a: Box<dyn Sdf>,
+    pub b: Box<dyn Sdf>,
+    pub k: f32,
+}
+
+impl SmoothUnion {
+    pub fn new(a: Box<dyn Sdf>, b: Box<dyn Sdf>, k: f32) -> Self {
+        SmoothUnion {
+            a,
+            b,
+            k,
+        }
+    }
+}
+
+impl Sdf for SmoothUnion {
+    fn distance(&self, p: Vec3) -> f32 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        let h = (0.5 + 0.5 * (d2 - d1) / self.k).clamp(0., 1.);
+        mix(d2, d1, h) - self.k * h * (1. - h)
+    }
+}
+
+// `a` with `b` carved out of it, blended over a distance of `k`
+pub struct SmoothSubtraction {
+    pub a: Box<dyn Sdf>,
+    pub b: Box<dyn Sdf>,
+    pub k: f32,
+}
+
+impl SmoothSubtraction {
+    pub fn new(a: Box<dyn Sdf>, b: Box<dyn Sdf>, k: f32) -> Self {
+        SmoothSubtraction {
+            a,
+            b,
+            k,
+        }
+    }
+}
+
+impl Sdf for SmoothSubtraction {
+    fn distance(&self, p: Vec3) -> f32 {
+        let d1 = self.a.distance(p);
+        let d2 = self.b.distance(p);
+        let h = (0.5 - 0.5 * (d1 + d2) / self.k).clamp(0., 1.);
+        mix(d1, -d2, h) + self.k * h * (1. - h)
+    }
+}
+
+// infinitely repeats the shape every `period` along each axis.
+// The shape should fit in one cell for the distance to stay correct.
+pub struct Repeat {
+    pub sdf: Box<dyn Sdf>,
+    pub period: Vec3,
+}
+
+impl Repeat {
+    pub fn new(sdf: Box<dyn Sdf>, period: Vec3) -> Self {
+        Repeat {
+            sdf,
+            period,
+        }
+    }
+}
+
+fn repeat_axis(x: f32, period: f32) -> f32 {
+    if period <= 0. {
+        x
+    } else {
+        x - period * (x / period).round()
+    }
+}
+
+impl Sdf for Repeat {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.sdf.distance(Vec3::new(
+            repeat_axis(p.x, self.period.x),
+            repeat_axis(p.y, self.period.y),
+            repeat_axis(p.z, self.period.z),
+        ))
+    }
+}
+
+// pushes the surface along its normal with perlin turbulence,
+// which is handy for rocks and other organic shapes.
+pub struct Displace {
+    pub sdf: Box<dyn Sdf>,
+    pub noise: Perlin,
+    pub scale: f32,
+    pub amplitude: f32,
+}
+
+impl Displace {
+    pub fn new(sdf: Box<dyn Sdf>, scale: f32, amplitude: f32) -> Self {
+        Displace {
+            sdf,
+            noise: Perlin::new(),
+            scale,
+            amplitude,
+        }
+    }
+}
+
+impl Sdf for Displace {
+    fn distance(&self, p: Vec3) -> f32 {
+        self.sdf.distance(p) + self.amplitude * self.noise.turb(p * self.scale)
+    }
+}
+
+pub struct SdfObject {
+    pub sdf: Box<dyn Sdf>,
+    // the shape must fit in this box, it's where the marching happens
+    pub bounds: Aabb,
+    pub material: Arc<dyn Material>,
+    pub max_steps: u32,
+    pub epsilon: f32,
+    // fraction of the distance we step by. Should be lowered below 1 for
+    // functions that overestimate the distance, like displaced ones.
+    pub step_scale: f32,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Box<dyn Sdf>, bounds: Aabb, material: Arc<dyn Material>) -> Self {
+        SdfObject {
+            sdf,
+            bounds,
+            material,
+            max_steps: 256,
+            epsilon: 0.0001,
+            step_scale: 1.,
+        }
+    }
+
+    fn normal(&self, p: Vec3) -> Vec3 {
+        // gradient of the distance, with the tetrahedron technique
+        let h = self.epsilon;
+        let k0 = Vec3::new(1., -1., -1.);
+        let k1 = Vec3::new(-1., -1., 1.);
+        let k2 = Vec3::new(-1., 1., -1.);
+        let k3 = Vec3::new(1., 1., 1.);
+        (
+            k0 * self.sdf.distance(p + k0 * h) +
+            k1 * self.sdf.distance(p + k1 * h) +
+            k2 * self.sdf.distance(p + k2 * h) +
+            k3 * self.sdf.distance(p + k3 * h)
+        ).unit_vector()
+    }
+}
+
+impl Hitable for SdfObject {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (t_enter, t_exit) = self.bounds.hit_range(r, t_min, t_max)?;
+
+        // march along the unit direction, so distances and t are comparable
+        let length = r.direction.length();
+        let direction = r.direction / length;
+        let mut s = t_enter * length;
+        let s_exit = t_exit * length;
+
+        for _ in 0..self.max_steps {
+            let point = r.origin + direction * s;
+            let distance = self.sdf.distance(point).abs();
+
+            if distance < self.epsilon {
+                let t = s / length;
+                if t <= t_min || t >= t_max {
+                    return None;
+                }
+                let normal = self.normal(point);
+                let center = (self.bounds.min + self.bounds.max) / 2.;
+                let (u, v) = get_sphere_uv(&(point - center).unit_vector());
+                let tangent = sphere_tangent(normal);
+                return Some(HitRecord {
+                    t,
+                    point,
+                    normal,
+                    tangent,
+                    bitangent: sphere_bitangent(normal, tangent),
+                    u,
+                    v,
+                    material: &*self.material,
+                });
+            }
+
+            s += distance * self.step_scale;
+            if s > s_exit {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self, _t0: f32, _t1: f32) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+}
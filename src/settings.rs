@@ -0,0 +1,100 @@
+// Render settings, read from the command line:
+//   --scene <name> --environment <file.hdr> --smoke <voxel grid file>
+//   --integrator <name> --width <pixels> --height <pixels> --samples <count>
+//   --max-diffuse <bounces> --max-specular <bounces> --max-transmission <bounces>
+//   --max-volume <bounces> --roulette-depth <bounces>
+
+use std::env;
+use std::io;
+use std::str::FromStr;
+use integrator::{integrator_names, PathTermination};
+use scenes::{scene_names};
+
+pub struct Settings {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub integrator: String,
+    pub scene: String,
+    // replaces the background of the scene, and lights it
+    pub environment: Option<String>,
+    // adds smoke with the densities of the grid to the scene
+    pub smoke: Option<String>,
+    pub termination: PathTermination,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            width: 600,
+            height: 400,
+            samples: 100,
+            integrator: String::from("mis"),
+            scene: String::from("two-spheres"),
+            environment: None,
+            smoke: None,
+            termination: PathTermination::default(),
+        }
+    }
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> io::Result<T> {
+    let value = value.ok_or_else(|| invalid_input(format!("missing value for {}", flag)))?;
+    value.parse().map_err(|_| invalid_input(format!("invalid value for {}: {}", flag, value)))
+}
+
+impl Settings {
+    pub fn from_args() -> io::Result<Self> {
+        Settings::parse(env::args().skip(1))
+    }
+
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> io::Result<Self> {
+        let mut settings = Settings::default();
+
+        while let Some(flag) = args.next() {
+            match flag.as_str() {
+                "--width" => settings.width = parse_value(&flag, args.next())?,
+                "--height" => settings.height = parse_value(&flag, args.next())?,
+                "--samples" => settings.samples = parse_value(&flag, args.next())?,
+                "--max-diffuse" => settings.termination.max_diffuse = parse_value(&flag, args.next())?,
+                "--max-specular" => settings.termination.max_specular = parse_value(&flag, args.next())?,
+                "--max-transmission" => settings.termination.max_transmission = parse_value(&flag, args.next())?,
+                "--max-volume" => settings.termination.max_volume = parse_value(&flag, args.next())?,
+                "--roulette-depth" => settings.termination.roulette_depth = parse_value(&flag, args.next())?,
+                "--integrator" => {
+                    let name: String = parse_value(&flag, args.next())?;
+                    let names = integrator_names();
+                    if !names.contains(&name.as_str()) {
+                        return Err(invalid_input(format!(
+                            "unknown integrator {}, expected one of: {}",
+                            name,
+                            names.join(", "),
+                        )));
+                    }
+                    settings.integrator = name;
+                },
+                "--scene" => {
+                    let name: String = parse_value(&flag, args.next())?;
+                    let names = scene_names();
+                    if !names.contains(&name.as_str()) {
+                        return Err(invalid_input(format!(
+                            "unknown scene {}, expected one of: {}",
+                            name,
+                            names.join(", "),
+                        )));
+                    }
+                    settings.scene = name;
+                },
+                "--environment" => settings.environment = Some(parse_value(&flag, args.next())?),
+                "--smoke" => settings.smoke = Some(parse_value(&flag, args.next())?),
+                _ => return Err(invalid_input(format!("unknown argument {}", flag))),
+            }
+        }
+
+        Ok(settings)
+    }
+}
@@ -0,0 +1,185 @@
+// Daylight sky from "A Practical Analytic Model for Daylight" (Preetham,
+// Shirley and Smits, 1999), with the sun as a separate light. The scene is
+// laid out with y up, the north towards -z and the east towards +x.
+
+use std::f32::consts::PI;
+use vec3::{Vec3};
+use environment::{Environment};
+use light::{SunLight};
+
+// converts the model's luminances, in kcd/m², to the scale of the renderer,
+// where a clear sky at noon is around 1
+const LUMINANCE_SCALE: f32 = 0.1;
+
+// illuminance from the sun outside of the atmosphere, in klux
+const SOLAR_ILLUMINANCE: f32 = 128.;
+
+// unit vector towards the sun, from its elevation above the horizon and
+// its azimuth from the north towards the east, in degrees
+pub fn sun_direction(elevation: f32, azimuth: f32) -> Vec3 {
+    let (elevation, azimuth) = (elevation.to_radians(), azimuth.to_radians());
+    Vec3::new(
+        azimuth.sin() * elevation.cos(),
+        elevation.sin(),
+        -azimuth.cos() * elevation.cos(),
+    )
+}
+
+// Unit vector towards the sun on `day_of_year` (1 to 365), at `solar_time`
+// hours (12 being noon) and at `latitude` degrees north.
+pub fn sun_direction_at(day_of_year: u32, solar_time: f32, latitude: f32) -> Vec3 {
+    let declination = (23.44f32).to_radians() * (2. * PI * (284 + day_of_year) as f32 / 365.).sin();
+    let hour_angle = (15. * (solar_time - 12.)).to_radians();
+    let latitude = latitude.to_radians();
+
+    let east = -declination.cos() * hour_angle.sin();
+    let north = latitude.cos() * declination.sin() - latitude.sin() * declination.cos() * hour_angle.cos();
+    let up = latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    Vec3::new(east, up, -north)
+}
+
+// coefficients of the Perez formula for one of Y, x and y
+type Perez = [f32; 5];
+
+fn perez(coefficients: &Perez, cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = *coefficients;
+    (1. + a * (b / cos_theta).exp()) * (1. + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+}
+
+fn polynomial(coefficients: [f32; 4], x: f32) -> f32 {
+    ((coefficients[0] * x + coefficients[1]) * x + coefficients[2]) * x + coefficients[3]
+}
+
+fn xyy_to_rgb(x: f32, y: f32, luminance: f32) -> Vec3 {
+    let big_x = x / y * luminance;
+    let big_z = (1. - x - y) / y * luminance;
+    Vec3::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+}
+
+// Hazy skies have a higher `turbidity`, from 2 for a very clear sky to
+// about 10. The ground below the horizon reflects the sky and the sun with
+// `ground_albedo`.
+pub struct PreethamSky {
+    // unit vector towards the sun
+    pub sun_direction: Vec3,
+    pub turbidity: f32,
+    pub ground_albedo: Vec3,
+    // Y, x and y at the zenith
+    zenith: [f32; 3],
+    perez: [Perez; 3],
+    ground: Vec3,
+}
+
+impl PreethamSky {
+    pub fn new(sun_direction: Vec3, turbidity: f32, ground_albedo: Vec3) -> Self {
+        let t = turbidity;
+        let sun_direction = sun_direction.unit_vector();
+        // the model breaks down with the sun below the horizon
+        let theta_s = sun_direction.y.max(0.).acos();
+
+        let chi = (4. / 9. - t / 120.) * (PI - 2. * theta_s);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x =
+            t * t * polynomial([0.00166, -0.00375, 0.00209, 0.], theta_s)
+            + t * polynomial([-0.02903, 0.06377, -0.03202, 0.00394], theta_s)
+            + polynomial([0.11693, -0.21196, 0.06052, 0.25886], theta_s);
+        let zenith_y =
+            t * t * polynomial([0.00275, -0.00610, 0.00317, 0.], theta_s)
+            + t * polynomial([-0.04214, 0.08970, -0.04153, 0.00516], theta_s)
+            + polynomial([0.15346, -0.26756, 0.06670, 0.26688], theta_s);
+
+        let perez = [
+            [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703],
+            [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452],
+            [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529],
+        ];
+
+        let mut sky = PreethamSky {
+            sun_direction,
+            turbidity,
+            ground_albedo,
+            zenith: [zenith_luminance, zenith_x, zenith_y],
+            perez,
+            ground: Vec3::zero(),
+        };
+        sky.ground = sky.ground_radiance();
+        sky
+    }
+
+    fn sky_radiance(&self, direction: Vec3) -> Vec3 {
+        // the formula diverges at the horizon
+        let cos_theta = direction.y.max(0.001);
+        let cos_gamma = direction.dot(self.sun_direction).clamp(-1., 1.);
+        let gamma = cos_gamma.acos();
+        let theta_s = self.sun_direction.y.max(0.).acos();
+
+        let mut values = [0.; 3];
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = self.zenith[i] * perez(&self.perez[i], cos_theta, gamma)
+                / perez(&self.perez[i], 1., theta_s);
+        }
+        let [luminance, x, y] = values;
+
+        let rgb = xyy_to_rgb(x, y, luminance * LUMINANCE_SCALE);
+        Vec3::new(rgb.x.max(0.), rgb.y.max(0.), rgb.z.max(0.))
+    }
+
+    // Light from the sun reaching the ground, through an air mass that gets
+    // larger towards the horizon, and reddened by Rayleigh and aerosol
+    // scattering.
+    pub fn sun_irradiance(&self) -> Vec3 {
+        let cos_zenith = self.sun_direction.y;
+        if cos_zenith <= 0. {
+            return Vec3::zero();
+        }
+
+        // Kasten and Young's formula
+        let zenith_degrees = cos_zenith.acos().to_degrees();
+        let air_mass = 1. / (cos_zenith + 0.50572 * (96.07995 - zenith_degrees).powf(-1.6364));
+
+        // Ångström's formula for the aerosols
+        let beta = 0.04608 * self.turbidity - 0.04586;
+        let transmittance = |wavelength: f32| {
+            let rayleigh = 0.008735 * wavelength.powf(-4.08);
+            let aerosol = beta * wavelength.powf(-1.3);
+            (-air_mass * (rayleigh + aerosol)).exp()
+        };
+
+        // at the wavelengths of red, green and blue, in micrometers
+        Vec3::new(transmittance(0.68), transmittance(0.55), transmittance(0.44))
+            * SOLAR_ILLUMINANCE * LUMINANCE_SCALE
+    }
+
+    // the sun as a light, to go along with the sky
+    pub fn sun_light(&self) -> SunLight {
+        SunLight::new(-self.sun_direction, self.sun_irradiance(), 0.53)
+    }
+
+    // the ground is lambertian, lit by the sun and the upper hemisphere of the sky
+    fn ground_radiance(&self) -> Vec3 {
+        let (n_theta, n_phi) = (32, 64);
+        let mut irradiance = Vec3::zero();
+        for i in 0..n_theta {
+            let theta = (i as f32 + 0.5) / n_theta as f32 * PI / 2.;
+            for j in 0..n_phi {
+                let phi = (j as f32 + 0.5) / n_phi as f32 * 2. * PI;
+                let direction = Vec3::new(theta.sin() * phi.cos(), theta.cos(), theta.sin() * phi.sin());
+                irradiance = irradiance + self.sky_radiance(direction) * theta.cos() * theta.sin();
+            }
+        }
+        irradiance = irradiance * (PI / 2. / n_theta as f32) * (2. * PI / n_phi as f32);
+        irradiance = irradiance + self.sun_irradiance() * self.sun_direction.y.max(0.);
+
+        self.ground_albedo * irradiance / PI
+    }
+}
+
+impl Environment for PreethamSky {
+    fn radiance(&self, direction: Vec3) -> Vec3 {
+        if direction.y < 0. { self.ground } else { self.sky_radiance(direction) }
+    }
+}
@@ -0,0 +1,160 @@
+// Subsurface scattering: light going through the surface of a translucent
+// object, bouncing many times inside and coming out somewhere else, which
+// softens the shading and bleeds color into the shadows. Marble, skin, wax
+// and milk look like painted plaster without it.
+//
+// The object is a closed surface of rough glass filled with a dense medium,
+// and paths take a random walk inside: they are scattered at exponentially
+// distributed distances until they get out through the surface. The walk
+// is one bounce per hit, so long walks need a high `--max-volume`.
+//
+// The channels share the extinction, the density of collisions, and only
+// differ by their albedo. Giving each its own would need weights that grow
+// wildly over the hundreds of steps of a walk. The albedo sets the color of
+// the light coming out, the extinction how far it spreads under the surface.
+
+use std::f32;
+use vec3::{Vec3};
+use ray::{Ray};
+use aabb::{Aabb};
+use hitable::{Hitable, HitRecord};
+use material::{HenyeyGreenstein};
+use microfacet::{RoughDielectric};
+use medium::{scatter_record, free_flight};
+use texture::{Texture, ConstantTexture};
+
+// Distance the boundary is looked for from. The steps of the walk can be
+// much shorter than the offset the integrators leave between a bounce and
+// the next hit, which would let paths slip through the surface near it.
+const WALK_EPSILON: f32 = 1e-5;
+// Added around the bounding box. `BvhTree` only looks for hits past the
+// same offset, and would miss the object from collisions near its box.
+const BOX_PADDING: f32 = 0.02;
+
+// A translucent object filling `boundary`, a closed surface with outward
+// normals, whose own material is not used. The medium has a mean free path,
+// in scene units, and an albedo per channel, the part of the light kept at
+// each collision, which can vary in space like the veins of marble.
+pub struct Subsurface {
+    pub boundary: Box<dyn Hitable>,
+    pub extinction: f32,
+    pub phase: HenyeyGreenstein,
+    pub surface: RoughDielectric,
+}
+
+impl Subsurface {
+    pub fn new(boundary: Box<dyn Hitable>, mean_free_path: f32, albedo: Box<dyn Texture>, refraction_index: f32) -> Self {
+        Subsurface {
+            boundary,
+            extinction: 1. / mean_free_path,
+            phase: HenyeyGreenstein::new(albedo, 0.),
+            surface: RoughDielectric::new(refraction_index, 0.1),
+        }
+    }
+
+    // From the scattering and absorption coefficients, per scene unit. The
+    // extinction is their mean over the channels, so they all spread as far
+    // under the surface: materials whose channels have very different mean
+    // free paths, like skin whose red goes deeper, bleed less color than
+    // they should.
+    pub fn from_coefficients(boundary: Box<dyn Hitable>, scattering: Vec3, absorption: Vec3, refraction_index: f32) -> Self {
+        let extinction = scattering + absorption;
+        Subsurface::new(
+            boundary,
+            3. / (extinction.x + extinction.y + extinction.z),
+            Box::new(ConstantTexture::new(scattering / extinction)),
+            refraction_index,
+        )
+    }
+
+    // Materials measured by Jensen et al. in "A Practical Model for
+    // Subsurface Light Transport", in millimeters: `millimeter` is the size
+    // of one in scene units. The coefficients are the reduced ones, for
+    // isotropic scattering.
+    pub fn marble(boundary: Box<dyn Hitable>, millimeter: f32) -> Self {
+        Subsurface::from_coefficients(
+            boundary,
+            Vec3::new(2.19, 2.62, 3.00) / millimeter,
+            Vec3::new(0.0021, 0.0041, 0.0071) / millimeter,
+            1.5,
+        )
+    }
+
+    pub fn skin(boundary: Box<dyn Hitable>, millimeter: f32) -> Self {
+        Subsurface::from_coefficients(
+            boundary,
+            Vec3::new(0.74, 0.88, 1.01) / millimeter,
+            Vec3::new(0.032, 0.17, 0.48) / millimeter,
+            1.4,
+        )
+    }
+
+    pub fn milk(boundary: Box<dyn Hitable>, millimeter: f32) -> Self {
+        Subsurface::from_coefficients(
+            boundary,
+            Vec3::new(2.55, 3.21, 3.77) / millimeter,
+            Vec3::new(0.0011, 0.0024, 0.014) / millimeter,
+            1.35,
+        )
+    }
+
+    // not measured, picked to look like a white candle
+    pub fn wax(boundary: Box<dyn Hitable>, millimeter: f32) -> Self {
+        Subsurface::from_coefficients(
+            boundary,
+            Vec3::new(2.475, 2.425, 2.25) / millimeter,
+            Vec3::new(0.025, 0.075, 0.25) / millimeter,
+            1.45,
+        )
+    }
+
+    // roughness of the surface, 0.1 by default
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.surface.roughness = Box::new(ConstantTexture::fromf(roughness));
+        self
+    }
+
+    // The first hit of the boundary, and whether the ray leaves through it.
+    // Rays leaving started inside and look for it past WALK_EPSILON, the
+    // others past t_min like with any other object.
+    fn boundary_hit(&self, r: &Ray, t_min: f32) -> Option<(HitRecord<'_>, bool)> {
+        let rec = self.boundary.hit(r, WALK_EPSILON, f32::MAX)?;
+        let leaving = r.direction.dot(rec.normal) > 0.;
+        if leaving || rec.t > t_min {
+            Some((rec, leaving))
+        } else {
+            self.boundary.hit(r, t_min, f32::MAX).map(|rec| (rec, false))
+        }
+    }
+}
+
+impl Hitable for Subsurface {
+    fn hit(&self, r: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord<'_>> {
+        let (rec, leaving) = self.boundary_hit(r, t_min)?;
+
+        // a ray started inside may collide before getting out
+        if leaving {
+            let t = (free_flight(self.extinction) / r.direction.length()).max(WALK_EPSILON);
+            if t < rec.t {
+                return if t < t_max { Some(scatter_record(r, t, &self.phase)) } else { None };
+            }
+        }
+
+        if rec.t < t_max { Some(HitRecord { material: &self.surface, ..rec }) } else { None }
+    }
+
+    // the surface blocks shadow rays, without taking a step of the walk
+    fn transmittance(&self, r: &Ray, t_min: f32, t_max: f32) -> f32 {
+        match self.boundary_hit(r, t_min) {
+            Some((rec, _)) if rec.t < t_max => 0.,
+            _ => 1.,
+        }
+    }
+
+    fn bounding_box(&self, t0: f32, t1: f32) -> Option<Aabb> {
+        self.boundary.bounding_box(t0, t1).map(|aabb| Aabb {
+            min: aabb.min - BOX_PADDING,
+            max: aabb.max + BOX_PADDING,
+        })
+    }
+}
@@ -1,11 +1,16 @@
 use vec3::{Vec3};
 use noises::{Perlin};
-use std::f32::consts::PI;
 use image;
 use std::path::Path;
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
   fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3;
+
+  // for the parameters that are a single number, like a roughness,
+  // clamped between 0 and 1. Grey textures are the usual choice.
+  fn scalar(&self, u: f32, v: f32, p: Vec3) -> f32 {
+    self.value(u, v, p).x.clamp(0., 1.)
+  }
 }
 
 pub struct ConstantTexture {
@@ -18,6 +23,10 @@ impl ConstantTexture {
       color
     }
   }
+
+  pub fn fromf(value: f32) -> Self {
+    ConstantTexture::new(Vec3::fromf(value))
+  }
 }
 
 impl Texture for ConstantTexture {
@@ -81,43 +90,55 @@ impl Texture for NoiseTexture {
   }
 }
 
+// the turbulence itself, grey, as a height for displacement and bump maps
+pub struct TurbulenceTexture {
+  noise: Perlin,
+  scale: f32
+}
 
-fn get_sphere_uv(p: &Vec3) -> (f32, f32) {
-  // phi "left-right" angle of the point on the sphere
-  let phi =  p.z.atan2(p.x);
-  // theta is the up-down angle of the point on the sphere
-  let theta = p.y.asin();
-
-  let uv = (
-    1. - (phi + PI) / (2. * PI),
-    (theta + PI / 2.) / PI
-  );
+impl TurbulenceTexture {
+  pub fn new(scale: f32) -> Self {
+    TurbulenceTexture {
+      noise: Perlin::new(),
+      scale
+    }
+  }
+}
 
-  uv
+impl Texture for TurbulenceTexture {
+  fn value(&self, _u: f32, _v: f32, p: Vec3) -> Vec3 {
+    Vec3::fromf(self.noise.turb(self.scale * p))
+  }
 }
 
-fn load_image(path: String) -> Vec<u8> {
-  let im = image::open(Path::new(path)).unwrap().to_rbg();
-  let rgb: Vec<u8> = im.raw_pixels();
-  rgb
+
+fn load_image(path: &str) -> (Vec<u8>, u32, u32) {
+  let im = image::open(Path::new(path)).unwrap().to_rgb();
+  let (width, height) = im.dimensions();
+  (im.into_raw(), width, height)
 }
 
-struct ImageTexture {
+pub struct ImageTexture {
   data: Vec<u8>,
   width: f32,
   height: f32
 }
 
-impl Texture for ImageTexture {
-  fn value(&self, u: f32, v: f32, p: Vec3) -> Vec3 {
-    let mut i = u * self.width;
-    let mut j = (1. - v) * self.height - 0.001;
-    if i < 0. {
-      i = 0.;
-    }
-    if j < 0. {
-      j = 0.;
+impl ImageTexture {
+  pub fn new(path: &str) -> Self {
+    let (data, width, height) = load_image(path);
+    ImageTexture {
+      data,
+      width: width as f32,
+      height: height as f32
     }
+  }
+}
+
+impl Texture for ImageTexture {
+  fn value(&self, u: f32, v: f32, _p: Vec3) -> Vec3 {
+    let i = (u * self.width).floor().clamp(0., self.width - 1.);
+    let j = ((1. - v) * self.height - 0.001).floor().clamp(0., self.height - 1.);
     let pixel_offset = 3. * i + 3. * self.width * j;
 
     let r = (self.data[(pixel_offset) as usize]) as f32 / 255.0;
@@ -73,6 +73,15 @@ impl Vec3 {
     pub fn sqrt(self) -> Vec3 {
         Vec3::new(self.x.sqrt(), self.y.sqrt(), self.z.sqrt())
     }
+
+    pub fn exp(self) -> Vec3 {
+        Vec3::new(self.x.exp(), self.y.exp(), self.z.exp())
+    }
+
+    // perceived brightness of a linear rgb color
+    pub fn luminance(self) -> f32 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
 }
 
 impl Add for Vec3 {